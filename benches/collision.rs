@@ -0,0 +1,206 @@
+//! Checks every pair of colliders against the spatial hash broad phase, on scenes
+//! with a player, a few dozen enemies and blocks and an increasing number of enemy
+//! lasers. Layers are set up like the game's, lasers only hit the player.
+//!
+//! The last column runs the game's `CollisionPlugin` on the same scene spawned into
+//! a bevy `World`, `src/collision.rs` and `src/spatial.rs` are included as is.
+//!
+//! cargo bench --bench collision
+
+#[path = "../src/collision.rs"]
+#[allow(dead_code, clippy::type_complexity)]
+mod collision;
+#[path = "../src/spatial.rs"]
+#[allow(dead_code)]
+mod spatial;
+
+use std::time::{Duration, Instant};
+
+use bevy::{app::Events, prelude::*};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use collision::{layers::*, CollisionPlugin, CollisionStarted, Shape};
+use spatial::SpatialHash;
+
+const WIDTH: f32 = 1000.;
+const HEIGHT: f32 = 1000.;
+const CELL_SIZE: f32 = 50.;
+const BLOCKS: usize = 20;
+const ENEMIES: usize = 30;
+const FRAMES: u32 = 10;
+
+struct Collider {
+    center: Vec2,
+    size: Vec2,
+    layer: u32,
+    mask: u32,
+}
+
+fn random_colliders(
+    rng: &mut StdRng,
+    count: usize,
+    size: Vec2,
+    layer: u32,
+    mask: u32,
+    out: &mut Vec<Collider>,
+) {
+    for _ in 0..count {
+        let center = Vec2::new(
+            rng.gen_range(-WIDTH / 2.0..WIDTH / 2.0),
+            rng.gen_range(-HEIGHT / 2.0..HEIGHT / 2.0),
+        );
+        out.push(Collider {
+            center,
+            size,
+            layer,
+            mask,
+        });
+    }
+}
+
+fn scene(lasers: usize) -> Vec<Collider> {
+    let mut rng = StdRng::seed_from_u64(lasers as u64);
+    let mut colliders = Vec::new();
+    random_colliders(
+        &mut rng,
+        1,
+        Vec2::new(40., 60.),
+        PLAYER,
+        ENEMY | BLOCK | ENEMY_LASER,
+        &mut colliders,
+    );
+    random_colliders(
+        &mut rng,
+        BLOCKS,
+        Vec2::new(75., 75.),
+        BLOCK,
+        PLAYER,
+        &mut colliders,
+    );
+    random_colliders(
+        &mut rng,
+        ENEMIES,
+        Vec2::new(60., 60.),
+        ENEMY,
+        PLAYER,
+        &mut colliders,
+    );
+    random_colliders(
+        &mut rng,
+        lasers,
+        Vec2::new(8., 24.),
+        ENEMY_LASER,
+        PLAYER,
+        &mut colliders,
+    );
+    colliders
+}
+
+fn overlaps(a: &Collider, b: &Collider) -> bool {
+    let distance = (a.center - b.center).abs();
+    let reach = (a.size + b.size) / 2.;
+    distance.x < reach.x && distance.y < reach.y
+}
+
+fn brute_force(colliders: &[Collider]) -> usize {
+    let mut contacts = 0;
+    for (i, a) in colliders.iter().enumerate() {
+        for b in colliders[i + 1..].iter() {
+            let interacts = a.mask & b.layer != 0 || b.mask & a.layer != 0;
+            if interacts && overlaps(a, b) {
+                contacts += 1;
+            }
+        }
+    }
+    contacts
+}
+
+fn spatial_hash(colliders: &[Collider]) -> usize {
+    let mut grid = SpatialHash::new(CELL_SIZE);
+    for collider in colliders.iter() {
+        grid.insert(
+            collider.center,
+            collider.size,
+            collider.layer,
+            collider.mask,
+        );
+    }
+    let mut contacts = 0;
+    grid.for_each_pair(|i, j| {
+        if overlaps(&colliders[i], &colliders[j]) {
+            contacts += 1;
+        }
+    });
+    contacts
+}
+
+/// Average time per frame and the contacts found.
+fn time(colliders: &[Collider], detect: fn(&[Collider]) -> usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut contacts = 0;
+    for _ in 0..FRAMES {
+        contacts = detect(colliders);
+    }
+    (start.elapsed() / FRAMES, contacts)
+}
+
+/// Average time per app update with the colliders spawned as entities, and the
+/// contacts started on the first update. The scene stands still, so later updates
+/// only keep the contacts up to date, like most frames in the game.
+fn time_world(colliders: &[Collider]) -> (Duration, usize) {
+    let mut app = App::build();
+    app.add_plugin(CollisionPlugin);
+    let mut app = app.app;
+    for collider in colliders.iter() {
+        app.world
+            .spawn()
+            .insert(Transform::from_translation(collider.center.extend(0.)))
+            .insert(collision::Collider::trigger(
+                Shape::Box(collider.size),
+                collider.layer,
+                collider.mask,
+            ));
+    }
+
+    app.update();
+    let started = app
+        .world
+        .get_resource::<Events<CollisionStarted>>()
+        .unwrap();
+    let contacts = started.get_reader().iter(started).count();
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        app.update();
+    }
+    (start.elapsed() / FRAMES, contacts)
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>16} {:>16} {:>19} {:>10}",
+        "colliders", "brute force", "spatial hash", "collision_detect", "contacts"
+    );
+    for &lasers in [100, 1000, 2000, 5000, 10000].iter() {
+        let colliders = scene(lasers);
+        let (brute_time, brute_contacts) = time(&colliders, brute_force);
+        let (hash_time, hash_contacts) = time(&colliders, spatial_hash);
+        let (world_time, world_contacts) = time_world(&colliders);
+        assert_eq!(
+            brute_contacts, hash_contacts,
+            "the broad phase missed or repeated contacts"
+        );
+        assert_eq!(
+            brute_contacts, world_contacts,
+            "collision_detect missed or repeated contacts"
+        );
+        println!(
+            "{:>10} {:>13.3} ms {:>13.3} ms {:>16.3} ms {:>10}",
+            colliders.len(),
+            brute_time.as_secs_f64() * 1000.,
+            hash_time.as_secs_f64() * 1000.,
+            world_time.as_secs_f64() * 1000.,
+            hash_contacts
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Runs a steady stream of enemy lasers through a bevy `World`: every frame the
+//! oldest lasers go away and as many new ones are fired. Compares spawning and
+//! despawning them against handing them out and releasing them with the game's
+//! `EntityPools`.
+//!
+//! `src/pool.rs` is included as is. The components it strips from released entities
+//! are stand-ins below, it only needs their types.
+//!
+//! cargo bench --bench pooling
+
+#[path = "../src/pool.rs"]
+#[allow(dead_code, unused_imports)]
+mod pool;
+
+mod animation {
+    use bevy::prelude::Entity;
+
+    pub struct AnimationFinished {
+        pub entity: Entity,
+    }
+    pub struct SpriteAnimation;
+}
+
+mod collision {
+    pub struct Collider;
+}
+
+mod events {
+    use bevy::prelude::Entity;
+
+    pub struct Despawned {
+        pub entity: Entity,
+    }
+}
+
+mod lifetime {
+    pub struct DespawnOutside;
+    pub struct Lifetime;
+}
+
+mod particles {
+    pub struct ParticleEmitter;
+}
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+use collision::Collider;
+use pool::{release, EntityPools, PoolKind};
+
+const FRAMES: u32 = 200;
+/// Frames a laser stays alive, so there are `LIFETIME * fired` lasers on screen.
+const LIFETIME: usize = 60;
+
+struct Laser;
+#[allow(dead_code)]
+struct FromPlayer;
+struct FromEnemy;
+#[allow(dead_code)]
+struct Explosion;
+struct Speed(f32, f32);
+
+/// Lasers fired each frame.
+struct Fired(usize);
+
+/// Lasers on screen, oldest first.
+#[derive(Default)]
+struct Live(VecDeque<Entity>);
+
+fn fire(mut laser: EntityCommands, i: usize) -> Entity {
+    laser
+        .insert_bundle(SpriteBundle {
+            transform: Transform::from_xyz(i as f32, 0., 0.),
+            ..Default::default()
+        })
+        .insert(Laser)
+        .insert(FromEnemy)
+        .insert(Speed(0., -300.))
+        .insert(Collider);
+    laser.id()
+}
+
+fn churn(mut commands: Commands, fired: Res<Fired>, mut live: ResMut<Live>) {
+    for i in 0..fired.0 {
+        if live.0.len() >= LIFETIME * fired.0 {
+            let entity = live.0.pop_front().unwrap();
+            commands.entity(entity).despawn();
+        }
+        let entity = fire(commands.spawn(), i);
+        live.0.push_back(entity);
+    }
+}
+
+fn pooled(
+    mut commands: Commands,
+    mut pools: ResMut<EntityPools>,
+    fired: Res<Fired>,
+    mut live: ResMut<Live>,
+) {
+    for i in 0..fired.0 {
+        if live.0.len() >= LIFETIME * fired.0 {
+            let entity = live.0.pop_front().unwrap();
+            release(&mut commands, entity);
+        }
+        let entity = fire(pools.spawn(&mut commands, PoolKind::EnemyLaser), i);
+        live.0.push_back(entity);
+    }
+}
+
+fn laser_movement(mut query: Query<(&Speed, &mut Transform), With<Laser>>) {
+    for (speed, mut tf) in query.iter_mut() {
+        tf.translation.x += speed.0 / 60.;
+        tf.translation.y += speed.1 / 60.;
+    }
+}
+
+/// Average time per frame, once the screen is full, and the entities the world ends with.
+fn time<S: System<In = (), Out = ()>>(fired: usize, step: S) -> (Duration, u32) {
+    let mut world = World::new();
+    world.insert_resource(Fired(fired));
+    world.insert_resource(Live::default());
+    world.insert_resource(EntityPools::default());
+    let mut stage = SystemStage::single_threaded()
+        .with_system(step)
+        .with_system(laser_movement.system());
+
+    for _ in 0..LIFETIME {
+        stage.run(&mut world);
+    }
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        stage.run(&mut world);
+    }
+    let elapsed = start.elapsed() / FRAMES;
+
+    let mut lasers = world.query_filtered::<(), With<Laser>>();
+    assert_eq!(lasers.iter(&world).count(), LIFETIME * fired);
+    (elapsed, world.entities().len())
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>10} {:>16} {:>16} {:>10}",
+        "fired", "on screen", "spawn/despawn", "pooled", "entities"
+    );
+    for &fired in [10, 50, 100, 200, 500].iter() {
+        let (churn_time, _) = time(fired, churn.system());
+        let (pooled_time, entities) = time(fired, pooled.system());
+        println!(
+            "{:>10} {:>10} {:>13.3} ms {:>13.3} ms {:>10}",
+            fired,
+            LIFETIME * fired,
+            churn_time.as_secs_f64() * 1000.,
+            pooled_time.as_secs_f64() * 1000.,
+            entities
+        );
+    }
+}
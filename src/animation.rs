@@ -0,0 +1,227 @@
+use bevy::prelude::*;
+
+use crate::game_time::GameTime;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<AnimationFinished>()
+            .add_system(animate_sprites.system().label("animation"))
+            .add_system(fit_height.system().after("animation"));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimationMode {
+    Loop,
+    /// Plays through once, then does `AnimationEnd`.
+    Once,
+    /// Plays forwards then backwards, forever.
+    PingPong,
+}
+
+/// What a `Once` animation does after its last frame.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimationEnd {
+    Despawn,
+    /// Stays on the last frame.
+    Hold,
+    /// Stays on the last frame and sends `AnimationFinished`.
+    Event,
+}
+
+/// A `Once` animation with `AnimationEnd::Event` reached its last frame.
+pub struct AnimationFinished {
+    pub entity: Entity,
+}
+
+/// Steps through frames `first..=last` at `fps`. Frames are `TextureAtlasSprite`
+/// indices, or indices into `AnimationMaterials` for entities drawn with plain sprites.
+pub struct SpriteAnimation {
+    pub first: u32,
+    pub last: u32,
+    pub fps: f32,
+    pub mode: AnimationMode,
+    pub end: AnimationEnd,
+    frame: u32,
+    since_frame: f32,
+    backwards: bool,
+    finished: bool,
+}
+
+impl SpriteAnimation {
+    pub fn new(first: u32, last: u32, fps: f32, mode: AnimationMode) -> Self {
+        Self {
+            first,
+            last,
+            fps,
+            mode,
+            end: AnimationEnd::Hold,
+            frame: first,
+            since_frame: 0.,
+            backwards: false,
+            finished: false,
+        }
+    }
+
+    pub fn once(first: u32, last: u32, fps: f32, end: AnimationEnd) -> Self {
+        Self {
+            end,
+            ..Self::new(first, last, fps, AnimationMode::Once)
+        }
+    }
+
+    /// Moves one frame on, returns whether a `Once` animation just ended.
+    fn advance(&mut self) -> bool {
+        if self.first >= self.last {
+            self.frame = self.first;
+            return self.mode == AnimationMode::Once;
+        }
+        match self.mode {
+            AnimationMode::Loop => {
+                self.frame = if self.frame >= self.last {
+                    self.first
+                } else {
+                    self.frame + 1
+                };
+                false
+            }
+            AnimationMode::Once => {
+                if self.frame >= self.last {
+                    true
+                } else {
+                    self.frame += 1;
+                    false
+                }
+            }
+            AnimationMode::PingPong => {
+                if self.backwards && self.frame <= self.first {
+                    self.backwards = false;
+                } else if !self.backwards && self.frame >= self.last {
+                    self.backwards = true;
+                }
+                if self.backwards {
+                    self.frame -= 1;
+                } else {
+                    self.frame += 1;
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Frames of a `SpriteAnimation` on an entity without a texture atlas.
+pub struct AnimationMaterials(pub Vec<Handle<ColorMaterial>>);
+
+/// Sizes the sprite to this height in world units, keeping its texture's aspect ratio,
+/// so frames drawn at different resolutions show at the same size.
+pub struct FitHeight(pub f32);
+
+fn animate_sprites(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut SpriteAnimation,
+        Option<&mut TextureAtlasSprite>,
+        Option<&AnimationMaterials>,
+        Option<&mut Handle<ColorMaterial>>,
+    )>,
+    mut animation_finished: EventWriter<AnimationFinished>,
+) {
+    for (entity, mut animation, atlas_sprite, frames, material) in query.iter_mut() {
+        if animation.finished || animation.fps <= 0. {
+            continue;
+        }
+        animation.since_frame += game_time.delta();
+        let frame_time = 1. / animation.fps;
+        while animation.since_frame >= frame_time && !animation.finished {
+            animation.since_frame -= frame_time;
+            if animation.advance() {
+                animation.finished = true;
+                match animation.end {
+                    AnimationEnd::Despawn => commands.entity(entity).despawn(),
+                    AnimationEnd::Hold => {}
+                    AnimationEnd::Event => animation_finished.send(AnimationFinished { entity }),
+                }
+            }
+        }
+
+        if let Some(mut atlas_sprite) = atlas_sprite {
+            atlas_sprite.index = animation.frame;
+        }
+        if let (Some(frames), Some(mut material)) = (frames, material) {
+            if let Some(frame) = frames.0.get(animation.frame as usize) {
+                if *material != *frame {
+                    *material = frame.clone();
+                }
+            }
+        }
+    }
+}
+
+fn fit_height(
+    color_materials: Res<Assets<ColorMaterial>>,
+    textures: Res<Assets<Texture>>,
+    mut query: Query<(&FitHeight, &Handle<ColorMaterial>, &mut Sprite)>,
+) {
+    for (fit, material, mut sprite) in query.iter_mut() {
+        let texture = color_materials
+            .get(material)
+            .and_then(|material| material.texture.as_ref())
+            .and_then(|texture| textures.get(texture));
+        if let Some(texture) = texture {
+            let size = Vec2::new(texture.size.width as f32, texture.size.height as f32);
+            sprite.resize_mode = SpriteResizeMode::Manual;
+            sprite.size = size * (fit.0 / size.y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(animation: &mut SpriteAnimation, steps: usize) -> Vec<u32> {
+        (0..steps)
+            .map(|_| {
+                animation.advance();
+                animation.frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_wraps_to_the_first_frame() {
+        let mut animation = SpriteAnimation::new(2, 4, 10., AnimationMode::Loop);
+        assert_eq!(animation.frame, 2);
+        assert_eq!(frames(&mut animation, 4), vec![3, 4, 2, 3]);
+    }
+
+    #[test]
+    fn once_ends_on_the_last_frame() {
+        let mut animation = SpriteAnimation::once(0, 2, 10., AnimationEnd::Hold);
+        assert!(!animation.advance());
+        assert!(!animation.advance());
+        assert!(animation.advance());
+        assert_eq!(animation.frame, 2);
+    }
+
+    #[test]
+    fn ping_pong_bounces_between_the_ends() {
+        let mut animation = SpriteAnimation::new(0, 2, 10., AnimationMode::PingPong);
+        assert_eq!(frames(&mut animation, 6), vec![1, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn single_frame_animations_stay_put() {
+        let mut looping = SpriteAnimation::new(3, 3, 10., AnimationMode::Loop);
+        assert!(!looping.advance());
+        assert_eq!(looping.frame, 3);
+        let mut once = SpriteAnimation::once(3, 3, 10., AnimationEnd::Event);
+        assert!(once.advance());
+        assert_eq!(once.frame, 3);
+    }
+}
@@ -1,40 +1,193 @@
-use bevy::{core::FixedTimestep, ecs::system::Command, math::const_m128, prelude::*};
-use rand::{thread_rng, Rng};
+use bevy::{
+    core::FixedTimestep,
+    ecs::system::Command,
+    math::{const_m128, const_vec2},
+    prelude::*,
+};
+use rand::Rng;
+use serde::Deserialize;
 
-use crate::{Block, BlockNumber, Materials, WindowSize, BLOCK_NUMBER};
+use crate::{
+    collision::{layers, Collider, Shape},
+    game_time::GameTime,
+    hazard::spawn_spikes,
+    population::{Population, Tracked},
+    spawn::{random_position, GameRng, Placement, SpawnBlockers},
+    Block, Crumbling, HitPoints, Materials, MovingPlatform, OneWay, Player, WindowSize,
+    BLOCK_NUMBER, CRUMBLE_RESPAWN,
+};
+
+/// Room kept free around a spawning block. Twice the block's size, so blocks whose
+/// texture isn't loaded yet (and have no size to check against) still can't overlap.
+const BLOCK_CLEARANCE: Vec2 = const_vec2!([150., 150.]);
+/// How far moving blocks go either side of where they spawn.
+const MOVING_BLOCK_RANGE: f32 = 100.;
 
 pub struct BlockPlugin;
 
 impl Plugin for BlockPlugin {
     fn build(&self, app: &mut bevy::prelude::AppBuilder) {
-        app.add_system(block_spawn.system());
+        app.add_system(block_spawn.system())
+            .add_system(moving_platform_movement.system().before("collision"))
+            .add_system(crumbling_blocks.system());
     }
 }
 
+#[derive(Deserialize, Clone)]
+pub enum BlockKind {
+    Solid,
+    OneWay,
+    Moving { path: Vec<(f32, f32)>, speed: f32 },
+    Crumbling,
+}
+
+pub fn spawn_block(
+    commands: &mut Commands,
+    materials: &Materials,
+    position: Vec2,
+    kind: &BlockKind,
+    hit_points: Option<u32>,
+) -> Entity {
+    let material = match kind {
+        BlockKind::Solid => materials.block.clone(),
+        BlockKind::OneWay => materials.block_one_way.clone(),
+        BlockKind::Moving { .. } => materials.block_moving.clone(),
+        BlockKind::Crumbling => materials.block_crumbling.clone(),
+    };
+    let mut block = commands.spawn_bundle(SpriteBundle {
+        material,
+        transform: Transform {
+            translation: position.extend(10.),
+            scale: Vec3::new(0.075, 0.075, 1.),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    block.insert(Block).insert(Collider::solid(
+        Shape::Sprite,
+        layers::BLOCK,
+        layers::PLAYER | layers::PLAYER_LASER | layers::ENEMY_LASER,
+    ));
+    if let Some(hit_points) = hit_points {
+        block.insert(HitPoints(hit_points));
+    }
+
+    match kind {
+        BlockKind::Solid => {}
+        BlockKind::OneWay => {
+            block.insert(OneWay);
+        }
+        BlockKind::Moving { path, speed } => {
+            block.insert(MovingPlatform {
+                path: path.iter().map(|&(x, y)| Vec2::new(x, y)).collect(),
+                speed: *speed,
+                target: 0,
+                delta: Vec2::ZERO,
+            });
+        }
+        BlockKind::Crumbling => {
+            block.insert(Crumbling::Solid);
+        }
+    }
+    block.id()
+}
+
 fn block_spawn(
     mut commands: Commands,
     win_size: Res<WindowSize>,
     mateirals: Res<Materials>,
-    mut block_number: ResMut<BlockNumber>,
+    mut population: ResMut<Population>,
+    mut game_rng: ResMut<GameRng>,
+    collider_query: Query<(&GlobalTransform, &Sprite), SpawnBlockers>,
+    player_query: Query<&Transform, With<Player>>,
 ) {
-    if (block_number.0 < BLOCK_NUMBER) {
-        let mut rng = thread_rng();
-        let w_span = win_size.width / 2. - 100.;
-        let h_span = win_size.height / 2. - 100.;
-        let x = rng.gen_range(-w_span..w_span) as f32;
-        let y = rng.gen_range(-h_span..h_span) as f32;
-
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: mateirals.block.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y, 10.),
-                    scale: Vec3::new(0.075, 0.075, 1.),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Block);
-        block_number.0 += 1;
+    if (population.blocks() < BLOCK_NUMBER) {
+        let rng = &mut game_rng.0;
+        let mut placement = Placement::new(
+            collider_query.iter(),
+            player_query
+                .single()
+                .ok()
+                .map(|tf| tf.translation.truncate()),
+        );
+
+        let roll = rng.gen_range(0..10);
+        // moving blocks need room for their whole path
+        let size = if roll == 9 {
+            BLOCK_CLEARANCE + Vec2::new(2. * MOVING_BLOCK_RANGE, 0.)
+        } else {
+            BLOCK_CLEARANCE
+        };
+        let wanted = random_position(&win_size, rng);
+        let (x, y) = match placement.place(rng, wanted, size, &win_size) {
+            Some(position) => (position.x, position.y),
+            None => return,
+        };
+
+        let kind = match roll {
+            0..=5 => BlockKind::Solid,
+            6 | 7 => BlockKind::OneWay,
+            8 => BlockKind::Crumbling,
+            _ => BlockKind::Moving {
+                path: vec![(x - MOVING_BLOCK_RANGE, y), (x + MOVING_BLOCK_RANGE, y)],
+                speed: 100.,
+            },
+        };
+        let hit_points = if rng.gen_bool(0.3) { Some(3) } else { None };
+        let spikes = matches!(kind, BlockKind::Solid) && rng.gen_bool(0.1);
+        let block = spawn_block(
+            &mut commands,
+            &mateirals,
+            Vec2::new(x, y),
+            &kind,
+            hit_points,
+        );
+        if spikes {
+            spawn_spikes(&mut commands, &mateirals, block);
+        }
+        population.track(block, Tracked::Block);
+    }
+}
+
+fn moving_platform_movement(
+    game_time: Res<GameTime>,
+    mut query: Query<(&mut MovingPlatform, &mut Transform)>,
+) {
+    for (mut platform, mut tf) in query.iter_mut() {
+        if platform.path.is_empty() {
+            continue;
+        }
+        let to_target = platform.path[platform.target] - tf.translation.truncate();
+        let step = platform.speed * game_time.delta();
+        let delta = if to_target.length() <= step {
+            platform.target = (platform.target + 1) % platform.path.len();
+            to_target
+        } else {
+            to_target.normalize() * step
+        };
+        tf.translation += delta.extend(0.);
+        platform.delta = delta;
+    }
+}
+
+fn crumbling_blocks(game_time: Res<GameTime>, mut query: Query<(&mut Crumbling, &mut Visible)>) {
+    for (mut crumbling, mut visible) in query.iter_mut() {
+        match *crumbling {
+            Crumbling::Solid => {}
+            Crumbling::Shaking(left) if left > game_time.delta() => {
+                *crumbling = Crumbling::Shaking(left - game_time.delta())
+            }
+            Crumbling::Shaking(_) => {
+                *crumbling = Crumbling::Broken(CRUMBLE_RESPAWN);
+                visible.is_visible = false;
+            }
+            Crumbling::Broken(left) if left > game_time.delta() => {
+                *crumbling = Crumbling::Broken(left - game_time.delta())
+            }
+            Crumbling::Broken(_) => {
+                *crumbling = Crumbling::Solid;
+                visible.is_visible = true;
+            }
+        }
     }
 }
@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::{
+    collision::{collider_for, layers, CollisionStarted, Contacts, Hitbox},
+    data::load_ron,
+    enemy::{behavior_for, BehaviorKind},
+    events::{EnemyKilled, LaserFired, LaserHit},
+    game_time::GameTime,
+    pattern::{fire_pattern, FirePattern, Shot},
+    pool::{release, EntityPools},
+    Boss, BossDying, Enemy, EnemyBehavior, ExplosionToSpawn, FromPlayer, HitPoints, Invulnerable,
+    Laser, Materials, Player, Speed, BOSSES_FILE,
+};
+
+const BOSS_EXPLOSION_INTERVAL: f32 = 0.15;
+const BOSS_EXPLOSIONS: u32 = 12;
+/// Seconds the player can't ram the boss again, or be hurt, after ramming it.
+const RAM_IFRAMES: f32 = 1.;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(boss_types_load.system())
+            .add_system(boss_phase.system())
+            .add_system(boss_fire.system())
+            .add_system(player_laser_hit_boss.system().after("collision"))
+            .add_system(player_ram_boss.system().after("collision"))
+            .add_system(boss_death.system());
+    }
+}
+
+/// Phases are checked in order, the first one whose `hp_above` is at or under
+/// the boss's remaining hit point fraction is active.
+#[derive(Deserialize)]
+pub struct BossPhase {
+    pub hp_above: f32,
+    pub behavior: BehaviorKind,
+    pub speed: f32,
+    pub fire_pattern: FirePattern,
+    pub fire_interval: f32,
+}
+
+#[derive(Deserialize)]
+pub struct BossSpec {
+    pub name: String,
+    pub sprite: String,
+    pub scale: f32,
+    pub hit_points: u32,
+    pub score: u32,
+    /// Collision shape, the sprite rect when missing.
+    #[serde(default)]
+    pub hitbox: Option<Hitbox>,
+    pub phases: Vec<BossPhase>,
+}
+
+pub struct BossType {
+    pub spec: BossSpec,
+    pub material: Handle<ColorMaterial>,
+}
+
+/// Every boss type from `BOSSES_FILE`, indexed by `Boss::kind`.
+pub struct BossTypes {
+    pub types: Vec<BossType>,
+}
+
+impl BossTypes {
+    pub fn by_name(&self, name: &str) -> Option<usize> {
+        self.types.iter().position(|t| t.spec.name == name)
+    }
+}
+
+fn boss_types_load(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let specs: Vec<BossSpec> = load_ron(BOSSES_FILE);
+    let types = specs
+        .into_iter()
+        .map(|spec| {
+            assert!(!spec.phases.is_empty(), "boss {} has no phases", spec.name);
+            BossType {
+                material: materials.add(asset_server.load(spec.sprite.as_str()).into()),
+                spec,
+            }
+        })
+        .collect();
+    commands.insert_resource(BossTypes { types });
+}
+
+pub fn spawn_boss(
+    commands: &mut Commands,
+    boss_types: &BossTypes,
+    kind: usize,
+    position: Vec2,
+) -> Entity {
+    let boss_type = &boss_types.types[kind];
+    let spec = &boss_type.spec;
+    let phase = &spec.phases[0];
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: boss_type.material.clone(),
+            transform: Transform {
+                translation: position.extend(10.),
+                scale: Vec3::new(spec.scale, spec.scale, 1.),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Enemy)
+        .insert(Boss {
+            kind,
+            max_hit_points: spec.hit_points,
+            phase: 0,
+            since_fire: 0.,
+        })
+        .insert(HitPoints(spec.hit_points))
+        .insert(collider_for(
+            spec.hitbox.as_ref(),
+            true,
+            layers::ENEMY,
+            layers::PLAYER | layers::PLAYER_LASER,
+        ))
+        .insert(behavior_for(phase.behavior, position, None))
+        .insert(Speed::from_speed(Vec2::new(phase.speed, -phase.speed)))
+        .id()
+}
+
+/// Switches movement and firing once hit points drop under the next threshold.
+fn boss_phase(
+    boss_types: Res<BossTypes>,
+    mut query: Query<(
+        &mut Boss,
+        &HitPoints,
+        &Transform,
+        &mut EnemyBehavior,
+        &mut Speed,
+    )>,
+) {
+    for (mut boss, hit_points, tf, mut behavior, mut speed) in query.iter_mut() {
+        let phases = &boss_types.types[boss.kind].spec.phases;
+        let fraction = hit_points.0 as f32 / boss.max_hit_points as f32;
+        let phase = phases
+            .iter()
+            .position(|phase| fraction >= phase.hp_above)
+            .unwrap_or(phases.len() - 1);
+
+        if phase != boss.phase {
+            boss.phase = phase;
+            boss.since_fire = 0.;
+            let phase = &phases[phase];
+            *behavior = behavior_for(phase.behavior, tf.translation.truncate(), None);
+            *speed = Speed::from_speed(Vec2::new(phase.speed, -phase.speed));
+        }
+    }
+}
+
+fn boss_fire(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut pools: ResMut<EntityPools>,
+    materials: Res<Materials>,
+    boss_types: Res<BossTypes>,
+    mut query: Query<(Entity, &mut Boss, &Transform), Without<BossDying>>,
+    player_query: Query<&Transform, (With<Player>, Without<Boss>)>,
+    mut laser_fired: EventWriter<LaserFired>,
+) {
+    let target = player_query
+        .single()
+        .ok()
+        .map(|tf| tf.translation.truncate());
+    for (entity, mut boss, tf) in query.iter_mut() {
+        let phase = &boss_types.types[boss.kind].spec.phases[boss.phase];
+        boss.since_fire += game_time.delta();
+        if boss.since_fire < phase.fire_interval {
+            continue;
+        }
+        boss.since_fire = 0.;
+        let shot = Shot {
+            origin: tf.translation.truncate(),
+            target,
+            time: game_time.seconds() as f32,
+        };
+        fire_pattern(
+            &mut commands,
+            &mut pools,
+            &materials,
+            &mut laser_fired,
+            &phase.fire_pattern,
+            entity,
+            &shot,
+        );
+    }
+}
+
+fn player_laser_hit_boss(
+    mut commands: Commands,
+    mut collision_started: EventReader<CollisionStarted>,
+    laser_query: Query<&Transform, (With<Laser>, With<FromPlayer>)>,
+    mut boss_query: Query<(&Transform, &Boss, &mut HitPoints), Without<BossDying>>,
+    boss_types: Res<BossTypes>,
+    mut enemy_killed: EventWriter<EnemyKilled>,
+    mut laser_hit: EventWriter<LaserHit>,
+) {
+    let mut lasers_spent: HashSet<Entity> = HashSet::new();
+    for CollisionStarted(pair) in collision_started.iter() {
+        let (laser_entity, boss_entity) = match pair.between(layers::PLAYER_LASER, layers::ENEMY) {
+            Some(entities) => entities,
+            None => continue,
+        };
+        if lasers_spent.contains(&laser_entity) {
+            continue;
+        }
+        let laser_tf = match laser_query.get(laser_entity) {
+            Ok(laser_tf) => laser_tf,
+            Err(_) => continue,
+        };
+        if let Ok((boss_tf, boss, mut hit_points)) = boss_query.get_mut(boss_entity) {
+            if hit_points.0 == 0 {
+                continue;
+            }
+            release(&mut commands, laser_entity);
+            lasers_spent.insert(laser_entity);
+            laser_hit.send(LaserHit {
+                position: laser_tf.translation,
+            });
+            hit_boss(
+                &mut commands,
+                &boss_types,
+                &mut enemy_killed,
+                boss_entity,
+                boss_tf,
+                boss,
+                &mut hit_points,
+            );
+        }
+    }
+}
+
+/// Ramming the boss hurts it like a laser does. The player bounces off unharmed and
+/// gets a moment of invulnerability, so staying on the boss hits it once per
+/// `RAM_IFRAMES`.
+fn player_ram_boss(
+    mut commands: Commands,
+    contacts: Res<Contacts>,
+    player_query: Query<Entity, (With<Player>, Without<Invulnerable>)>,
+    mut boss_query: Query<(&Transform, &Boss, &mut HitPoints), Without<BossDying>>,
+    boss_types: Res<BossTypes>,
+    mut enemy_killed: EventWriter<EnemyKilled>,
+) {
+    let player_entity = match player_query.single() {
+        Ok(player_entity) => player_entity,
+        Err(_) => return,
+    };
+    for boss_entity in contacts.of(player_entity) {
+        if let Ok((boss_tf, boss, mut hit_points)) = boss_query.get_mut(boss_entity) {
+            if hit_points.0 == 0 {
+                continue;
+            }
+            hit_boss(
+                &mut commands,
+                &boss_types,
+                &mut enemy_killed,
+                boss_entity,
+                boss_tf,
+                boss,
+                &mut hit_points,
+            );
+            commands
+                .entity(player_entity)
+                .insert(Invulnerable(RAM_IFRAMES));
+            // one hit per ram, even with several bosses
+            return;
+        }
+    }
+}
+
+/// Takes a hit point off the boss, starting its death once none are left.
+fn hit_boss(
+    commands: &mut Commands,
+    boss_types: &BossTypes,
+    enemy_killed: &mut EventWriter<EnemyKilled>,
+    entity: Entity,
+    tf: &Transform,
+    boss: &Boss,
+    hit_points: &mut HitPoints,
+) {
+    hit_points.0 -= 1;
+    if hit_points.0 == 0 {
+        // stop moving and firing, boss_death takes it from here
+        commands
+            .entity(entity)
+            .remove::<Enemy>()
+            .remove::<EnemyBehavior>()
+            .insert(BossDying {
+                since_explosion: 0.,
+                explosions_left: BOSS_EXPLOSIONS,
+            });
+        enemy_killed.send(EnemyKilled {
+            position: tf.translation,
+            score: boss_types.types[boss.kind].spec.score,
+            boss: true,
+        });
+    }
+}
+
+/// Chains explosions over the boss before despawning it.
+fn boss_death(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, &Sprite, &mut BossDying)>,
+) {
+    let mut rng = thread_rng();
+    for (entity, tf, sprite, mut dying) in query.iter_mut() {
+        dying.since_explosion += game_time.delta();
+        if dying.since_explosion < BOSS_EXPLOSION_INTERVAL {
+            continue;
+        }
+        dying.since_explosion = 0.;
+
+        if dying.explosions_left == 0 {
+            commands.entity(entity).despawn();
+            commands.spawn().insert(ExplosionToSpawn(tf.translation));
+            continue;
+        }
+        dying.explosions_left -= 1;
+        let size = sprite.size * Vec2::from(tf.scale.abs());
+        let offset = Vec2::new(
+            rng.gen_range(-0.5..0.5) * size.x,
+            rng.gen_range(-0.5..0.5) * size.y,
+        );
+        commands
+            .spawn()
+            .insert(ExplosionToSpawn(tf.translation + offset.extend(1.)));
+    }
+}
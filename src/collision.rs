@@ -0,0 +1,287 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::spatial::SpatialHash;
+
+/// Side of the broad phase grid cells, a bit under the size of an enemy.
+const COLLISION_CELL_SIZE: f32 = 50.;
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Contacts::default())
+            .add_event::<CollisionStarted>()
+            .add_system(
+                collision_detect
+                    .system()
+                    .label("collision")
+                    .after("player_movement"),
+            );
+    }
+}
+
+/// Collision layer bits, used for both `Collider::layer` and `Collider::mask`.
+pub mod layers {
+    /// The player's body, which stands on blocks and rams enemies.
+    pub const PLAYER: u32 = 1 << 0;
+    pub const ENEMY: u32 = 1 << 1;
+    pub const BLOCK: u32 = 1 << 2;
+    pub const PLAYER_LASER: u32 = 1 << 3;
+    pub const ENEMY_LASER: u32 = 1 << 4;
+    pub const HAZARD: u32 = 1 << 5;
+    /// Where the player can be hit by lasers, see `PlayerHurtbox`.
+    pub const PLAYER_HURTBOX: u32 = 1 << 6;
+}
+
+#[derive(Clone, Copy)]
+pub enum Shape {
+    /// The entity's sprite rect, scaled with its transform.
+    Sprite,
+    /// A box of this size in world units.
+    Box(Vec2),
+    /// A circle of this radius in world units.
+    Circle(f32),
+}
+
+/// Lets an entity collide with others. Two colliders touch when either one's `mask`
+/// has a bit of the other's `layer`. Triggers only report contacts, solid pairs are
+/// also pushed apart by whoever handles them, see `player_block_collide`.
+///
+/// Positions come from `Transform`. Children, like spikes on a block, use their
+/// `GlobalTransform`, which lags a frame behind their parent.
+#[derive(Clone, Copy)]
+pub struct Collider {
+    pub shape: Shape,
+    /// Shape center relative to the entity, in world units.
+    pub offset: Vec2,
+    pub layer: u32,
+    pub mask: u32,
+    pub trigger: bool,
+}
+
+impl Collider {
+    pub fn solid(shape: Shape, layer: u32, mask: u32) -> Self {
+        Self {
+            shape,
+            offset: Vec2::ZERO,
+            layer,
+            mask,
+            trigger: false,
+        }
+    }
+
+    pub fn trigger(shape: Shape, layer: u32, mask: u32) -> Self {
+        Self {
+            trigger: true,
+            ..Self::solid(shape, layer, mask)
+        }
+    }
+
+    /// Center and size of the collider's bounding box in the world.
+    pub fn bounds(&self, tf: &Transform, sprite: Option<&Sprite>) -> (Vec2, Vec2) {
+        let center = tf.translation.truncate() + self.offset;
+        let size = match self.shape {
+            Shape::Sprite => sprite.map_or(Vec2::ZERO, |sprite| {
+                sprite.size * Vec2::from(tf.scale.abs())
+            }),
+            Shape::Box(size) => size,
+            Shape::Circle(radius) => Vec2::splat(radius * 2.),
+        };
+        (center, size)
+    }
+}
+
+/// Collision shape as written in the data files, in world units, independent of the
+/// sprite so transparent padding around it doesn't count.
+#[derive(Deserialize, Clone, Copy)]
+pub enum HitboxShape {
+    Box(f32, f32),
+    Circle(f32),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct Hitbox {
+    pub shape: HitboxShape,
+    #[serde(default)]
+    pub offset: (f32, f32),
+}
+
+impl Hitbox {
+    /// The offset mirrored left to right when `facing` is negative, for shapes that
+    /// flip with their sprite.
+    pub fn facing_offset(&self, facing: f32) -> Vec2 {
+        let x = if facing < 0. {
+            -self.offset.0
+        } else {
+            self.offset.0
+        };
+        Vec2::new(x, self.offset.1)
+    }
+
+    pub fn collider(&self, trigger: bool, layer: u32, mask: u32) -> Collider {
+        let shape = match self.shape {
+            HitboxShape::Box(width, height) => Shape::Box(Vec2::new(width, height)),
+            HitboxShape::Circle(radius) => Shape::Circle(radius),
+        };
+        Collider {
+            shape,
+            offset: Vec2::new(self.offset.0, self.offset.1),
+            layer,
+            mask,
+            trigger,
+        }
+    }
+}
+
+/// Uses `hitbox` when there is one, the sprite rect otherwise.
+pub fn collider_for(hitbox: Option<&Hitbox>, trigger: bool, layer: u32, mask: u32) -> Collider {
+    match hitbox {
+        Some(hitbox) => hitbox.collider(trigger, layer, mask),
+        None if trigger => Collider::trigger(Shape::Sprite, layer, mask),
+        None => Collider::solid(Shape::Sprite, layer, mask),
+    }
+}
+
+/// A collider placed in the world for one frame of collision checks.
+struct Placed {
+    entity: Entity,
+    collider: Collider,
+    center: Vec2,
+    size: Vec2,
+}
+
+impl Placed {
+    fn overlaps(&self, other: &Placed) -> bool {
+        match (self.collider.shape, other.collider.shape) {
+            (Shape::Circle(a), Shape::Circle(b)) => (self.center - other.center).length() < a + b,
+            (Shape::Circle(radius), _) => circle_overlaps_box(self.center, radius, other),
+            (_, Shape::Circle(radius)) => circle_overlaps_box(other.center, radius, self),
+            _ => {
+                let distance = (self.center - other.center).abs();
+                let reach = (self.size + other.size) / 2.;
+                distance.x < reach.x && distance.y < reach.y
+            }
+        }
+    }
+}
+
+fn circle_overlaps_box(center: Vec2, radius: f32, other: &Placed) -> bool {
+    let half = other.size / 2.;
+    let closest = center.max(other.center - half).min(other.center + half);
+    (center - closest).length() < radius
+}
+
+/// Two colliding entities and their layers, `a` is always the smaller entity.
+#[derive(Clone, Copy)]
+pub struct CollisionPair {
+    pub a: Entity,
+    pub b: Entity,
+    pub a_layer: u32,
+    pub b_layer: u32,
+}
+
+impl CollisionPair {
+    /// The pair ordered as (entity on `first` layer, entity on `second` layer),
+    /// when it is between those two layers.
+    pub fn between(&self, first: u32, second: u32) -> Option<(Entity, Entity)> {
+        if self.a_layer & first != 0 && self.b_layer & second != 0 {
+            Some((self.a, self.b))
+        } else if self.b_layer & first != 0 && self.a_layer & second != 0 {
+            Some((self.b, self.a))
+        } else {
+            None
+        }
+    }
+}
+
+/// Sent the first frame two colliders touch.
+pub struct CollisionStarted(pub CollisionPair);
+
+/// Every pair of colliders touching this frame.
+#[derive(Default)]
+pub struct Contacts {
+    pairs: HashMap<(Entity, Entity), CollisionPair>,
+}
+
+impl Contacts {
+    /// Entities touching `entity`.
+    pub fn of(&self, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.pairs.values().filter_map(move |pair| {
+            if pair.a == entity {
+                Some(pair.b)
+            } else if pair.b == entity {
+                Some(pair.a)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn collision_detect(
+    mut contacts: ResMut<Contacts>,
+    query: Query<(
+        Entity,
+        &Collider,
+        &Transform,
+        Option<&Sprite>,
+        Option<(&Parent, &GlobalTransform)>,
+    )>,
+    mut collision_started: EventWriter<CollisionStarted>,
+) {
+    let placed: Vec<Placed> = query
+        .iter()
+        .map(|(entity, collider, tf, sprite, child)| {
+            let (center, size) = match child {
+                Some((_, global)) => collider.bounds(&Transform::from(*global), sprite),
+                None => collider.bounds(tf, sprite),
+            };
+            Placed {
+                entity,
+                collider: *collider,
+                center,
+                size,
+            }
+        })
+        .collect();
+
+    let mut grid = SpatialHash::new(COLLISION_CELL_SIZE);
+    for collider in placed.iter() {
+        grid.insert(
+            collider.center,
+            collider.size,
+            collider.collider.layer,
+            collider.collider.mask,
+        );
+    }
+
+    let mut touching = HashSet::new();
+    grid.for_each_pair(|i, j| {
+        let (first, second) = (&placed[i], &placed[j]);
+        if !first.overlaps(second) {
+            return;
+        }
+        let (a, b) = if first.entity < second.entity {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let key = (a.entity, b.entity);
+        touching.insert(key);
+        if let Entry::Vacant(entry) = contacts.pairs.entry(key) {
+            let pair = CollisionPair {
+                a: a.entity,
+                b: b.entity,
+                a_layer: a.collider.layer,
+                b_layer: b.collider.layer,
+            };
+            entry.insert(pair);
+            collision_started.send(CollisionStarted(pair));
+        }
+    });
+
+    contacts.pairs.retain(|key, _| touching.contains(key));
+}
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::CommandQueue, prelude::*, window::ReceivedCharacter};
+
+use crate::{WindowSize, WINDOW_TITLE};
+
+const CONSOLE_HEIGHT: f32 = 40.;
+const CONSOLE_Z: f32 = 110.;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Console::default())
+            .add_console_command("help", "help: lists commands", console_help)
+            .add_console_command("set", "set <name> <value>: changes a tunable", console_set)
+            .add_startup_system_to_stage("game_setup_stage", console_spawn.system())
+            .add_system(
+                console_input
+                    .system()
+                    .label("console")
+                    .before("player_movement"),
+            )
+            .add_system(console_run.exclusive_system());
+    }
+}
+
+/// Runs a command with its arguments, the text it returns goes to the console.
+pub type CommandFn = fn(&mut World, &[&str]) -> Result<String, String>;
+
+struct ConsoleCommand {
+    help: &'static str,
+    run: CommandFn,
+}
+
+/// Every command and `set` variable plugins registered, by name.
+#[derive(Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<&'static str, ConsoleCommand>,
+    vars: BTreeMap<&'static str, fn(&mut World, f32)>,
+}
+
+/// Lets plugins extend the console from their `build`.
+pub trait ConsoleAppExt {
+    fn add_console_command(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        run: CommandFn,
+    ) -> &mut Self;
+    /// Makes `set <name> <value>` call `set`.
+    fn add_console_var(&mut self, name: &'static str, set: fn(&mut World, f32)) -> &mut Self;
+}
+
+impl ConsoleAppExt for AppBuilder {
+    fn add_console_command(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        run: CommandFn,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .commands
+            .insert(name, ConsoleCommand { help, run });
+        self
+    }
+
+    fn add_console_var(&mut self, name: &'static str, set: fn(&mut World, f32)) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .vars
+            .insert(name, set);
+        self
+    }
+}
+
+/// Drop-down console opened with the backtick key. There is no font to draw text with,
+/// so the line being typed and the last output go in the window title and the log.
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    /// Entered lines waiting for `console_run`.
+    pending: Vec<String>,
+    last_output: String,
+}
+
+impl Console {
+    fn print(&mut self, output: String) {
+        info!("{}", output);
+        self.last_output = output;
+    }
+}
+
+struct ConsolePanel;
+
+/// Runs `f` with `Commands` for `world` and applies them afterwards, for commands
+/// that spawn or despawn.
+pub fn with_commands<R>(world: &mut World, f: impl FnOnce(&mut Commands, &World) -> R) -> R {
+    let mut queue = CommandQueue::default();
+    let result = {
+        let mut commands = Commands::new(&mut queue, world);
+        f(&mut commands, world)
+    };
+    queue.apply(world);
+    result
+}
+
+/// Parses a command argument, naming it in the error.
+pub fn parse_arg<T: std::str::FromStr>(
+    args: &[&str],
+    index: usize,
+    name: &str,
+) -> Result<T, String> {
+    let arg = args.get(index).ok_or_else(|| format!("missing {}", name))?;
+    arg.parse()
+        .map_err(|_| format!("`{}` is not a valid {}", arg, name))
+}
+
+fn console_spawn(
+    mut commands: Commands,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    win_size: Res<WindowSize>,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: color_materials.add(Color::rgba(0., 0., 0., 0.7).into()),
+            sprite: Sprite::new(Vec2::new(win_size.width, CONSOLE_HEIGHT)),
+            transform: Transform::from_xyz(
+                0.,
+                win_size.height / 2. - CONSOLE_HEIGHT / 2.,
+                CONSOLE_Z,
+            ),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(ConsolePanel);
+}
+
+/// Typing into an open console doesn't also move the player, keys are swallowed
+/// before `player_movement` and every other system ordered after "console" sees them.
+fn console_input(
+    mut console: ResMut<Console>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut windows: ResMut<Windows>,
+    mut panel_query: Query<&mut Visible, With<ConsolePanel>>,
+) {
+    let toggled = keyboard_input.just_pressed(KeyCode::Grave);
+    if toggled {
+        console.open = !console.open;
+        for mut visible in panel_query.iter_mut() {
+            visible.is_visible = console.open;
+        }
+    }
+    if !console.open {
+        received_characters.iter().for_each(drop);
+        if toggled {
+            if let Some(window) = windows.get_primary_mut() {
+                window.set_title(WINDOW_TITLE.to_string());
+            }
+        }
+        return;
+    }
+
+    for received in received_characters.iter() {
+        if !received.char.is_control() && received.char != '`' {
+            console.input.push(received.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        if !line.trim().is_empty() {
+            info!("> {}", line);
+            console.pending.push(line);
+        }
+    }
+
+    let keys: Vec<KeyCode> = keyboard_input
+        .get_pressed()
+        .chain(keyboard_input.get_just_released())
+        .cloned()
+        .collect();
+    for key in keys {
+        keyboard_input.reset(key);
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(format!("> {}_   {}", console.input, console.last_output));
+    }
+}
+
+/// Runs entered lines with full world access, so commands can touch anything.
+fn console_run(world: &mut World) {
+    let lines = match world.get_resource_mut::<Console>() {
+        Some(mut console) if !console.pending.is_empty() => std::mem::take(&mut console.pending),
+        _ => return,
+    };
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+        let run = world
+            .get_resource::<ConsoleCommands>()
+            .and_then(|commands| commands.commands.get(name))
+            .map(|command| command.run);
+        let output = match run {
+            Some(run) => match run(world, args) {
+                Ok(output) => output,
+                Err(error) => format!("{}: {}", name, error),
+            },
+            None => format!("unknown command `{}`, try `help`", name),
+        };
+        if let Some(mut console) = world.get_resource_mut::<Console>() {
+            console.print(output);
+        }
+    }
+}
+
+fn console_help(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    let commands = world
+        .get_resource::<ConsoleCommands>()
+        .ok_or("no commands")?;
+    let help: Vec<&str> = commands
+        .commands
+        .values()
+        .map(|command| command.help)
+        .collect();
+    Ok(help.join(" | "))
+}
+
+fn console_set(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let commands = world
+        .get_resource::<ConsoleCommands>()
+        .ok_or("no variables")?;
+    let name = match args.first() {
+        Some(name) => *name,
+        None => {
+            let names: Vec<&str> = commands.vars.keys().cloned().collect();
+            return Ok(format!("variables: {}", names.join(", ")));
+        }
+    };
+    let set = *commands
+        .vars
+        .get(name)
+        .ok_or_else(|| format!("unknown variable `{}`", name))?;
+    let value: f32 = parse_arg(args, 1, "value")?;
+    set(world, value);
+    Ok(format!("{} = {}", name, value))
+}
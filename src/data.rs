@@ -0,0 +1,22 @@
+use std::{env, fs, path::PathBuf};
+
+use serde::de::DeserializeOwned;
+
+/// Reads and parses a RON file from the assets folder.
+/// Game data is needed before anything can spawn, so a missing or broken file is fatal.
+pub fn load_ron<T: DeserializeOwned>(path: &str) -> T {
+    try_load_ron(path).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// `load_ron` for files named at runtime, where a bad name shouldn't end the game.
+pub fn try_load_ron<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let full_path = env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join("assets")
+        .join(path);
+    let contents = fs::read_to_string(&full_path)
+        .map_err(|e| format!("could not read {}: {}", full_path.display(), e))?;
+    ron::de::from_str(&contents)
+        .map_err(|e| format!("could not parse {}: {}", full_path.display(), e))
+}
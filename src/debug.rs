@@ -0,0 +1,243 @@
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{
+    collision::Collider,
+    director::Director,
+    pool::{EntityPools, PoolKind},
+    Block, Enemy, Explosion, Laser, Player, PlayerMotion, Speed, WindowSize, GROUND_HEIGHT,
+    WINDOW_TITLE,
+};
+
+const DEBUG_Z: f32 = 90.;
+const DEBUG_LINE_WIDTH: f32 = 2.;
+/// `Speed` lines are drawn this many seconds of movement long.
+const VELOCITY_LOOKAHEAD: f32 = 0.2;
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(DebugOverlay::default())
+            .add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_startup_system(debug_materials.system())
+            .add_system(debug_toggle.system().after("console"))
+            .add_system(debug_draw.system().after("player_block_collide"))
+            .add_system(debug_counters.system().before("console"));
+    }
+}
+
+/// Collider outlines, `Speed` vectors, the ground line and entity counts, toggled with F3.
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub on: bool,
+}
+
+struct DebugMaterials {
+    solid: Handle<ColorMaterial>,
+    trigger: Handle<ColorMaterial>,
+    grounded: Handle<ColorMaterial>,
+    velocity: Handle<ColorMaterial>,
+    ground: Handle<ColorMaterial>,
+}
+
+/// Overlay sprite, moved to a new line every frame and hidden when there are more
+/// of them than lines to draw.
+struct DebugShape;
+
+/// A line of the overlay, as the sprite drawing it.
+struct Line {
+    material: Handle<ColorMaterial>,
+    center: Vec2,
+    length: f32,
+    angle: f32,
+}
+
+fn debug_materials(mut commands: Commands, mut color_materials: ResMut<Assets<ColorMaterial>>) {
+    commands.insert_resource(DebugMaterials {
+        solid: color_materials.add(Color::rgb(0.2, 0.6, 1.).into()),
+        trigger: color_materials.add(Color::rgb(1., 0.9, 0.2).into()),
+        grounded: color_materials.add(Color::rgb(0.2, 1., 0.3).into()),
+        velocity: color_materials.add(Color::rgb(1., 0.3, 1.).into()),
+        ground: color_materials.add(Color::rgb(1., 0.4, 0.2).into()),
+    });
+}
+
+fn debug_toggle(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut windows: ResMut<Windows>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        overlay.on = !overlay.on;
+        if !overlay.on {
+            if let Some(window) = windows.get_primary_mut() {
+                window.set_title(WINDOW_TITLE.to_string());
+            }
+        }
+    }
+}
+
+fn push_line(lines: &mut Vec<Line>, material: &Handle<ColorMaterial>, from: Vec2, to: Vec2) {
+    let delta = to - from;
+    let length = delta.length();
+    if length == 0. {
+        return;
+    }
+    lines.push(Line {
+        material: material.clone(),
+        center: (from + to) / 2.,
+        length,
+        angle: delta.y.atan2(delta.x),
+    });
+}
+
+fn push_outline(lines: &mut Vec<Line>, material: &Handle<ColorMaterial>, center: Vec2, size: Vec2) {
+    let half = size / 2.;
+    let corners = [
+        center + Vec2::new(-half.x, -half.y),
+        center + Vec2::new(half.x, -half.y),
+        center + Vec2::new(half.x, half.y),
+        center + Vec2::new(-half.x, half.y),
+    ];
+    for i in 0..corners.len() {
+        push_line(
+            lines,
+            material,
+            corners[i],
+            corners[(i + 1) % corners.len()],
+        );
+    }
+}
+
+/// Runs once blocks have pushed the player out, so outlines match what was collided.
+/// Child colliders are drawn where they are in the world, like `collision_detect` sees them.
+fn debug_draw(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    debug_materials: Res<DebugMaterials>,
+    win_size: Res<WindowSize>,
+    mut shape_query: Query<
+        (
+            &mut Sprite,
+            &mut Transform,
+            &mut Handle<ColorMaterial>,
+            &mut Visible,
+        ),
+        With<DebugShape>,
+    >,
+    collider_query: Query<
+        (
+            &Collider,
+            &Transform,
+            Option<&Sprite>,
+            Option<&PlayerMotion>,
+            Option<(&Parent, &GlobalTransform)>,
+        ),
+        Without<DebugShape>,
+    >,
+    moving_query: Query<(&Transform, &Speed), Without<DebugShape>>,
+) {
+    let mut lines = Vec::new();
+    if overlay.on {
+        for (collider, tf, sprite, motion, child) in collider_query.iter() {
+            let (center, size) = match child {
+                Some((_, global)) => collider.bounds(&Transform::from(*global), sprite),
+                None => collider.bounds(tf, sprite),
+            };
+            let material = match motion {
+                Some(motion) if motion.grounded => &debug_materials.grounded,
+                _ if collider.trigger => &debug_materials.trigger,
+                _ => &debug_materials.solid,
+            };
+            push_outline(&mut lines, material, center, size);
+        }
+
+        for (tf, speed) in moving_query.iter() {
+            let position = tf.translation.truncate();
+            let to = position + Vec2::new(speed.0, speed.1) * VELOCITY_LOOKAHEAD;
+            push_line(&mut lines, &debug_materials.velocity, position, to);
+        }
+
+        let ground_y = -win_size.height + GROUND_HEIGHT;
+        push_line(
+            &mut lines,
+            &debug_materials.ground,
+            Vec2::new(-win_size.width / 2., ground_y),
+            Vec2::new(win_size.width / 2., ground_y),
+        );
+    }
+
+    let mut lines = lines.into_iter();
+    for (mut sprite, mut tf, mut material, mut visible) in shape_query.iter_mut() {
+        match lines.next() {
+            Some(line) => {
+                sprite.size = Vec2::new(line.length, DEBUG_LINE_WIDTH);
+                tf.translation = line.center.extend(DEBUG_Z);
+                tf.rotation = Quat::from_rotation_z(line.angle);
+                *material = line.material;
+                visible.is_visible = true;
+            }
+            None => visible.is_visible = false,
+        }
+    }
+    for line in lines {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: line.material,
+                sprite: Sprite::new(Vec2::new(line.length, DEBUG_LINE_WIDTH)),
+                transform: Transform {
+                    translation: line.center.extend(DEBUG_Z),
+                    rotation: Quat::from_rotation_z(line.angle),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(DebugShape);
+    }
+}
+
+/// Entity counts, the director's difficulty level and FPS go in the window title, there
+/// is no font to draw text with.
+/// Pooled entities waiting to be handed out again aren't in the other counts.
+fn debug_counters(
+    overlay: Res<DebugOverlay>,
+    diagnostics: Res<Diagnostics>,
+    pools: Res<EntityPools>,
+    director: Res<Director>,
+    mut windows: ResMut<Windows>,
+    player_query: Query<Entity, With<Player>>,
+    enemy_query: Query<Entity, With<Enemy>>,
+    laser_query: Query<Entity, With<Laser>>,
+    block_query: Query<Entity, With<Block>>,
+    explosion_query: Query<Entity, With<Explosion>>,
+) {
+    if !overlay.on {
+        return;
+    }
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.average())
+        .unwrap_or(0.);
+    let pooled = pools.free(PoolKind::PlayerLaser)
+        + pools.free(PoolKind::EnemyLaser)
+        + pools.free(PoolKind::Explosion);
+    let title = format!(
+        "{} | players {} enemies {} lasers {} blocks {} explosions {} pooled {} | difficulty {:.2} cap {} | {:.0} fps",
+        WINDOW_TITLE,
+        player_query.iter().count(),
+        enemy_query.iter().count(),
+        laser_query.iter().count(),
+        block_query.iter().count(),
+        explosion_query.iter().count(),
+        pooled,
+        director.level,
+        director.enemy_cap(),
+        fps
+    );
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(title);
+    }
+}
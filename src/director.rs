@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{game_time::GameTime, Kills, PlayerState};
+
+/// Seconds between difficulty adjustments.
+const DIRECTOR_INTERVAL: f32 = 5.;
+/// Kill rate is measured over this many seconds.
+const KILL_WINDOW: f64 = 30.;
+/// The player counts as cruising once they haven't died for this long.
+const CRUISING_TIME: f64 = 20.;
+const START_LEVEL: f32 = 0.3;
+
+const MIN_ENEMY_CAP: f32 = 3.;
+const MAX_ENEMY_CAP: f32 = 10.;
+const MAX_SPAWN_INTERVAL: f32 = 1.5;
+const MIN_SPAWN_INTERVAL: f32 = 0.4;
+const MAX_FIRE_INTERVAL: f32 = 2.;
+const MIN_FIRE_INTERVAL: f32 = 0.6;
+const MIN_SPEED_SCALE: f32 = 0.7;
+const MAX_SPEED_SCALE: f32 = 1.5;
+
+pub struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Director::default())
+            .add_system(director_watch.system())
+            .add_system(director_adjust.system());
+    }
+}
+
+/// Tunes enemy pressure to how the player is doing.
+/// `level` goes from 0. (easiest) to 1. (hardest), every knob is interpolated from it.
+pub struct Director {
+    pub level: f32,
+    pub deaths: u32,
+    pub last_death: f64,
+    kill_times: VecDeque<f64>,
+    deaths_at_last_adjust: u32,
+    since_adjust: f32,
+}
+
+impl Default for Director {
+    fn default() -> Self {
+        Self {
+            level: START_LEVEL,
+            deaths: 0,
+            last_death: 0.,
+            kill_times: VecDeque::new(),
+            deaths_at_last_adjust: 0,
+            since_adjust: 0.,
+        }
+    }
+}
+
+impl Director {
+    fn lerp(&self, easy: f32, hard: f32) -> f32 {
+        easy + (hard - easy) * self.level
+    }
+
+    pub fn enemy_cap(&self) -> u32 {
+        self.lerp(MIN_ENEMY_CAP, MAX_ENEMY_CAP).round() as u32
+    }
+
+    pub fn spawn_interval(&self) -> f32 {
+        self.lerp(MAX_SPAWN_INTERVAL, MIN_SPAWN_INTERVAL)
+    }
+
+    pub fn fire_interval(&self) -> f32 {
+        self.lerp(MAX_FIRE_INTERVAL, MIN_FIRE_INTERVAL)
+    }
+
+    pub fn speed_scale(&self) -> f32 {
+        self.lerp(MIN_SPEED_SCALE, MAX_SPEED_SCALE)
+    }
+
+    /// Kills per second over the last `KILL_WINDOW` seconds.
+    pub fn kill_rate(&self) -> f32 {
+        self.kill_times.len() as f32 / KILL_WINDOW as f32
+    }
+
+    /// Eases off after a death, pushes harder while the player is cruising,
+    /// never leaving 0. to 1.
+    fn next_level(&self, now: f64) -> f32 {
+        let died = self.deaths > self.deaths_at_last_adjust;
+        let cruising = now - self.last_death > CRUISING_TIME;
+        let delta = if died {
+            -0.15
+        } else if cruising && self.kill_rate() > 0.2 {
+            0.1
+        } else if cruising {
+            0.05
+        } else {
+            0.
+        };
+        (self.level + delta).clamp(0., 1.)
+    }
+}
+
+/// Records deaths and kills as they happen.
+fn director_watch(
+    game_time: Res<GameTime>,
+    mut director: ResMut<Director>,
+    mut was_on: Local<bool>,
+    mut last_kills: Local<u32>,
+    player_state: Res<PlayerState>,
+    kills: Res<Kills>,
+) {
+    let now = game_time.seconds();
+    if *was_on && !player_state.on {
+        director.deaths += 1;
+        director.last_death = now;
+    }
+    *was_on = player_state.on;
+
+    for _ in *last_kills..kills.0 {
+        director.kill_times.push_back(now);
+    }
+    *last_kills = kills.0;
+    while let Some(&oldest) = director.kill_times.front() {
+        if now - oldest <= KILL_WINDOW {
+            break;
+        }
+        director.kill_times.pop_front();
+    }
+}
+
+fn director_adjust(game_time: Res<GameTime>, mut director: ResMut<Director>) {
+    director.since_adjust += game_time.delta();
+    if director.since_adjust < DIRECTOR_INTERVAL {
+        return;
+    }
+    director.since_adjust = 0.;
+
+    let level = director.next_level(game_time.seconds());
+    director.deaths_at_last_adjust = director.deaths;
+    if level != director.level {
+        director.level = level;
+        info!(
+            "difficulty {:.2}: cap {} spawn {:.2}s fire {:.2}s speed x{:.2}",
+            level,
+            director.enemy_cap(),
+            director.spawn_interval(),
+            director.fire_interval(),
+            director.speed_scale()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knobs_span_their_bounds() {
+        let mut director = Director {
+            level: 0.,
+            ..Default::default()
+        };
+        assert_eq!(director.enemy_cap(), MIN_ENEMY_CAP as u32);
+        assert_eq!(director.spawn_interval(), MAX_SPAWN_INTERVAL);
+        assert_eq!(director.fire_interval(), MAX_FIRE_INTERVAL);
+        assert_eq!(director.speed_scale(), MIN_SPEED_SCALE);
+
+        director.level = 1.;
+        assert_eq!(director.enemy_cap(), MAX_ENEMY_CAP as u32);
+        assert!((director.spawn_interval() - MIN_SPAWN_INTERVAL).abs() < 1e-6);
+        assert!((director.fire_interval() - MIN_FIRE_INTERVAL).abs() < 1e-6);
+        assert!((director.speed_scale() - MAX_SPEED_SCALE).abs() < 1e-6);
+    }
+
+    #[test]
+    fn level_never_drops_below_zero() {
+        let director = Director {
+            level: 0.05,
+            deaths: 1,
+            last_death: 100.,
+            ..Default::default()
+        };
+        assert_eq!(director.next_level(100.), 0.);
+    }
+
+    #[test]
+    fn level_never_rises_above_one() {
+        let mut director = Director {
+            level: 0.98,
+            ..Default::default()
+        };
+        director.kill_times.extend((0..20).map(|i| i as f64));
+        assert_eq!(director.next_level(CRUISING_TIME + 1.), 1.);
+    }
+
+    #[test]
+    fn level_holds_until_the_player_cruises() {
+        let director = Director {
+            last_death: 10.,
+            ..Default::default()
+        };
+        assert_eq!(director.next_level(10. + CRUISING_TIME / 2.), START_LEVEL);
+        assert!(director.next_level(10. + CRUISING_TIME + 1.) > START_LEVEL);
+    }
+}
@@ -1,111 +1,366 @@
-use bevy::{core::FixedTimestep, ecs::system::Command, math::const_m128, prelude::*};
-use rand::{thread_rng, Rng};
+use bevy::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution, thread_rng, Rng};
+use serde::Deserialize;
 
 use crate::{
-    ActiveEnemies, Enemy, FromEnemy, Laser, Materials, Speed, WindowSize, SCALE, TIME_STEP,
+    collision::{collider_for, layers, Hitbox},
+    console::{parse_arg, with_commands, ConsoleAppExt},
+    data::load_ron,
+    director::Director,
+    events::LaserFired,
+    game_time::GameTime,
+    lifetime::DespawnOutside,
+    pattern::{fire_pattern, FirePattern, Shot},
+    pool::{release, EntityPools},
+    population::{Population, Tracked},
+    Block, Enemy, EnemyBehavior, EnemyKind, FromEnemy, HitPoints, Laser, Materials, Player,
+    SpawnTelegraph, Speed, SwoopState, ENEMY_TYPES_FILE,
 };
 
+const HOVER_AMPLITUDE: f32 = 100.;
+const HOVER_FREQUENCY: f32 = 2.;
+const SWOOP_WAIT: f32 = 2.;
+const SWOOP_SPEED: f32 = 400.;
+
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut bevy::prelude::AppBuilder) {
-        app.add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(1.0))
-                .with_system(enemy_spawn.system()),
-        )
-        // .add_system_set(
-        //     SystemSet::new()
-        //         .with_run_criteria(FixedTimestep::step(1.0))
-        //         .with_system(enemy_fire.system()),
-        // )
-        .add_system(enemy_laser_movement.system())
-        .add_system(enemy_movement.system());
+        app.add_startup_system(enemy_types_load.system())
+            .add_console_command(
+                "spawn",
+                "spawn <type|enemy> <x> <y>: spawns an enemy, `enemy` picks a random type",
+                console_spawn,
+            )
+            .add_console_command(
+                "clear",
+                "clear <enemies|lasers>: despawns them all",
+                console_clear,
+            )
+            .add_system(enemy_fire.system())
+            .add_system(enemy_laser_movement.system())
+            .add_system(enemy_movement.system());
     }
 }
 
-fn enemy_spawn(
+/// Movement behavior named in the enemy data, turned into an `EnemyBehavior` at spawn.
+#[derive(Deserialize, Clone, Copy)]
+pub enum BehaviorKind {
+    Fall,
+    Patrol,
+    Chase,
+    Hover,
+    Swoop,
+}
+
+#[derive(Deserialize)]
+pub struct EnemyTypeSpec {
+    pub name: String,
+    pub sprite: String,
+    pub scale: f32,
+    pub hit_points: u32,
+    pub speed: (f32, f32),
+    pub behavior: BehaviorKind,
+    pub fire_pattern: FirePattern,
+    pub score: u32,
+    pub weight: u32,
+    /// Most enemies of this type alive at once, unlimited when missing.
+    #[serde(default)]
+    pub max_alive: Option<u32>,
+    /// Collision shape, the sprite rect when missing.
+    #[serde(default)]
+    pub hitbox: Option<Hitbox>,
+}
+
+pub struct EnemyType {
+    pub spec: EnemyTypeSpec,
+    pub material: Handle<ColorMaterial>,
+}
+
+/// Every enemy type from `ENEMY_TYPES_FILE`, indexed by `EnemyKind`.
+pub struct EnemyTypes {
+    pub types: Vec<EnemyType>,
+    weights: WeightedIndex<u32>,
+}
+
+impl EnemyTypes {
+    pub fn get(&self, kind: &EnemyKind) -> &EnemyTypeSpec {
+        &self.types[kind.0].spec
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<usize> {
+        self.types.iter().position(|t| t.spec.name == name)
+    }
+
+    pub fn choose<R: Rng>(&self, rng: &mut R) -> usize {
+        self.weights.sample(rng)
+    }
+}
+
+fn enemy_types_load(
     mut commands: Commands,
-    mut active_enemies: ResMut<ActiveEnemies>,
-    win_size: Res<WindowSize>,
-    mateirals: Res<Materials>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    if active_enemies.0 < 5 {
-        let mut rng = thread_rng();
-        let w_span = win_size.width / 2. - 100.;
-        let h_span = win_size.height / 2. - 100.;
-        let x = rng.gen_range(-w_span..w_span) as f32;
-        let y = rng.gen_range(-h_span..h_span) as f32;
-
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: mateirals.enemy.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y, 10.),
-                    scale: Vec3::new(0.075, 0.075, 1.),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Enemy)
-            .insert(Speed::from_speed(Vec2::new(0., -100.)));
+    let specs: Vec<EnemyTypeSpec> = load_ron(ENEMY_TYPES_FILE);
+    let weights = WeightedIndex::new(specs.iter().map(|spec| spec.weight))
+        .expect("enemy types need at least one positive weight");
+    let types = specs
+        .into_iter()
+        .map(|spec| EnemyType {
+            material: materials.add(asset_server.load(spec.sprite.as_str()).into()),
+            spec,
+        })
+        .collect();
+    commands.insert_resource(EnemyTypes { types, weights });
+}
 
-        active_enemies.0 += 1;
+/// Builds the movement state for `kind`, starting from `position`.
+pub fn behavior_for(
+    kind: BehaviorKind,
+    position: Vec2,
+    patrol_block: Option<Entity>,
+) -> EnemyBehavior {
+    match (kind, patrol_block) {
+        (BehaviorKind::Fall, _) | (BehaviorKind::Patrol, None) => EnemyBehavior::Fall,
+        (BehaviorKind::Patrol, Some(block)) => EnemyBehavior::Patrol {
+            block,
+            direction: 1.,
+        },
+        (BehaviorKind::Chase, _) => EnemyBehavior::Chase,
+        (BehaviorKind::Hover, _) => EnemyBehavior::Hover {
+            origin: position,
+            phase: 0.,
+        },
+        (BehaviorKind::Swoop, _) => EnemyBehavior::Swoop {
+            home: position,
+            state: SwoopState::Waiting(SWOOP_WAIT),
+        },
     }
 }
+
+/// Spawns an enemy of type `kind`. Patrolling enemies walk on `patrol_block`,
+/// and fall instead when there is none.
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    enemy_types: &EnemyTypes,
+    kind: usize,
+    position: Vec2,
+    patrol_block: Option<Entity>,
+) -> Entity {
+    let enemy_type = &enemy_types.types[kind];
+    let spec = &enemy_type.spec;
+    let behavior = behavior_for(spec.behavior, position, patrol_block);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: enemy_type.material.clone(),
+            transform: Transform {
+                translation: position.extend(10.),
+                scale: Vec3::new(spec.scale, spec.scale, 1.),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Enemy)
+        .insert(EnemyKind(kind))
+        .insert(HitPoints(spec.hit_points))
+        .insert(behavior)
+        .insert(Speed::from_speed(Vec2::new(spec.speed.0, spec.speed.1)))
+        .insert(collider_for(
+            spec.hitbox.as_ref(),
+            true,
+            layers::ENEMY,
+            layers::PLAYER | layers::PLAYER_LASER,
+        ))
+        .insert(DespawnOutside::below_window())
+        .id()
+}
 fn enemy_fire(
+    game_time: Res<GameTime>,
     mut commands: Commands,
+    mut since_fire: Local<f32>,
+    mut pools: ResMut<EntityPools>,
+    director: Res<Director>,
     materials: Res<Materials>,
-    enemy_query: Query<&Transform, With<Enemy>>,
+    enemy_types: Res<EnemyTypes>,
+    enemy_query: Query<(Entity, &Transform, &EnemyKind), With<Enemy>>,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    mut laser_fired: EventWriter<LaserFired>,
 ) {
-    for &tf in enemy_query.iter() {
-        let x = tf.translation.x;
-        let y = tf.translation.y;
-
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.enemy_laser.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y - 15., 0.),
-                    scale: Vec3::new(SCALE, -SCALE, 1.),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Laser)
-            .insert(FromEnemy)
-            .insert(Speed::default());
+    *since_fire += game_time.delta();
+    if *since_fire < director.fire_interval() {
+        return;
+    }
+    *since_fire = 0.;
+
+    let target = player_query
+        .single()
+        .ok()
+        .map(|tf| tf.translation.truncate());
+    for (entity, tf, enemy_kind) in enemy_query.iter() {
+        let shot = Shot {
+            origin: tf.translation.truncate(),
+            target,
+            time: game_time.seconds() as f32,
+        };
+        fire_pattern(
+            &mut commands,
+            &mut pools,
+            &materials,
+            &mut laser_fired,
+            &enemy_types.get(enemy_kind).fire_pattern,
+            entity,
+            &shot,
+        );
     }
 }
 
 fn enemy_laser_movement(
-    mut commands: Commands,
-    win_size: Res<WindowSize>,
-    mut laser_query: Query<(Entity, &Speed, &mut Transform), (With<Laser>, With<FromEnemy>)>,
+    game_time: Res<GameTime>,
+    mut laser_query: Query<(&Speed, &mut Transform), (With<Laser>, With<FromEnemy>)>,
 ) {
-    for (entity, speed, mut tf) in laser_query.iter_mut() {
-        tf.translation.y -= speed.0 * TIME_STEP;
-        if tf.translation.y < -win_size.height / 2. - 50. {
-            commands.entity(entity).despawn();
-        }
+    for (speed, mut tf) in laser_query.iter_mut() {
+        tf.translation.x += speed.0 * game_time.delta();
+        tf.translation.y += speed.1 * game_time.delta();
+    }
+}
+
+/// Moves `tf` at most `step` towards `target`, returns true once it gets there.
+fn move_towards(tf: &mut Transform, target: Vec2, step: f32) -> bool {
+    let to_target = target - tf.translation.truncate();
+    if to_target.length() <= step {
+        tf.translation.x = target.x;
+        tf.translation.y = target.y;
+        true
+    } else {
+        tf.translation += (to_target.normalize() * step).extend(0.);
+        false
     }
 }
 
 fn enemy_movement(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut Transform, &Speed), With<Enemy>>,
-    mut active_enemies: ResMut<ActiveEnemies>,
-    win_size: Res<WindowSize>,
+    game_time: Res<GameTime>,
+    mut query: Query<
+        (&mut Transform, &Speed, &Sprite, &mut EnemyBehavior),
+        (With<Enemy>, Without<Player>, Without<Block>),
+    >,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    block_query: Query<(&Transform, &Sprite), (With<Block>, Without<Enemy>)>,
+    director: Res<Director>,
 ) {
-    let now = time.seconds_since_startup() as f32;
+    let step = game_time.delta() * director.speed_scale();
+    let player_position = player_query
+        .single()
+        .ok()
+        .map(|tf| tf.translation.truncate());
 
-    for (entity, mut tf, speed) in query.iter_mut() {
-        tf.translation.y += speed.1 * TIME_STEP;
+    for (mut tf, speed, sprite, mut behavior) in query.iter_mut() {
+        let mut next_behavior = None;
+        match &mut *behavior {
+            EnemyBehavior::Fall => {
+                tf.translation.y += speed.1 * step;
+            }
+            EnemyBehavior::Patrol { block, direction } => {
+                if let Ok((block_tf, block_sprite)) = block_query.get(*block) {
+                    let size = sprite.size * Vec2::from(tf.scale.abs());
+                    let block_size = block_sprite.size * Vec2::from(block_tf.scale.abs());
+                    let left = block_tf.translation.x - block_size.x / 2.;
+                    let right = block_tf.translation.x + block_size.x / 2.;
 
-        if (tf.translation.y < -win_size.height / 2.) {
-            commands.entity(entity).despawn();
-            active_enemies.0 -= 1;
+                    tf.translation.x += *direction * speed.0 * step;
+                    if tf.translation.x > right {
+                        tf.translation.x = right;
+                        *direction = -1.;
+                    } else if tf.translation.x < left {
+                        tf.translation.x = left;
+                        *direction = 1.;
+                    }
+                    tf.translation.y = block_tf.translation.y + block_size.y / 2. + size.y / 2.;
+                } else {
+                    // the block is gone, fall off
+                    next_behavior = Some(EnemyBehavior::Fall);
+                }
+            }
+            EnemyBehavior::Chase => {
+                if let Some(player_position) = player_position {
+                    let dx = player_position.x - tf.translation.x;
+                    tf.translation.x += dx.signum() * dx.abs().min(speed.0 * step);
+                }
+            }
+            EnemyBehavior::Hover { origin, phase } => {
+                *phase += HOVER_FREQUENCY * step;
+                tf.translation.x = origin.x + HOVER_AMPLITUDE * phase.sin();
+                tf.translation.y = origin.y + HOVER_AMPLITUDE / 4. * (2. * *phase).sin();
+            }
+            EnemyBehavior::Swoop { home, state } => match state {
+                SwoopState::Waiting(left) => {
+                    *left -= step;
+                    if *left <= 0. {
+                        match player_position {
+                            Some(player_position) => *state = SwoopState::Diving(player_position),
+                            None => *left = SWOOP_WAIT,
+                        }
+                    }
+                }
+                SwoopState::Diving(target) => {
+                    if move_towards(&mut tf, *target, SWOOP_SPEED * step) {
+                        *state = SwoopState::Retreating;
+                    }
+                }
+                SwoopState::Retreating => {
+                    if move_towards(&mut tf, *home, speed.0 * step) {
+                        *state = SwoopState::Waiting(SWOOP_WAIT);
+                    }
+                }
+            },
+        }
+        if let Some(next_behavior) = next_behavior {
+            *behavior = next_behavior;
         }
     }
 }
+
+fn console_spawn(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name = *args.first().ok_or("missing enemy type")?;
+    let x: f32 = parse_arg(args, 1, "x")?;
+    let y: f32 = parse_arg(args, 2, "y")?;
+    let enemy_types = world.get_resource::<EnemyTypes>().ok_or("no enemy types")?;
+    let kind = if name == "enemy" {
+        enemy_types.choose(&mut thread_rng())
+    } else {
+        enemy_types
+            .by_name(name)
+            .ok_or_else(|| format!("unknown enemy type `{}`", name))?
+    };
+    let entity = with_commands(world, |commands, world| {
+        let enemy_types = world.get_resource::<EnemyTypes>().unwrap();
+        spawn_enemy(commands, enemy_types, kind, Vec2::new(x, y), None)
+    });
+    if let Some(mut population) = world.get_resource_mut::<Population>() {
+        population.track(entity, Tracked::Enemy(kind));
+    }
+    Ok(format!("spawned enemy {} at {} {}", kind, x, y))
+}
+
+fn console_clear(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let what = *args.first().ok_or("missing what to clear")?;
+    let entities: Vec<Entity> = match what {
+        "enemies" => world
+            .query_filtered::<Entity, Or<(With<Enemy>, With<SpawnTelegraph>)>>()
+            .iter(world)
+            .collect(),
+        "lasers" => world
+            .query_filtered::<Entity, With<Laser>>()
+            .iter(world)
+            .collect(),
+        _ => return Err(format!("can't clear `{}`", what)),
+    };
+    // pooled lasers go back to their pool
+    with_commands(world, |commands, _| {
+        for entity in entities.iter() {
+            release(commands, *entity);
+        }
+    });
+    Ok(format!("cleared {} {}", entities.len(), what))
+}
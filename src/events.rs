@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+
+use crate::{ExplosionToSpawn, Kills, Score};
+
+pub struct GameEventsPlugin;
+
+impl Plugin for GameEventsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<EnemyKilled>()
+            .add_event::<PlayerHit>()
+            .add_event::<PlayerDied>()
+            .add_event::<BlockDestroyed>()
+            .add_event::<LaserFired>()
+            .add_event::<LaserHit>()
+            .add_event::<Landed>()
+            .add_event::<Jumped>()
+            .add_event::<Despawned>()
+            .add_system(score_kills.system())
+            .add_system(explode_on_destroy.system());
+    }
+}
+
+/// An enemy or boss was destroyed by the player. The entity is already despawned,
+/// or for bosses, going through its death explosions.
+pub struct EnemyKilled {
+    pub position: Vec3,
+    pub score: u32,
+    pub boss: bool,
+}
+
+/// Something hit the player. Whatever handles it decides what the hit does.
+pub struct PlayerHit {
+    pub player: Entity,
+    pub position: Vec3,
+}
+
+/// The player was despawned and will respawn after `PLAYER_RESPAWM_DELAY`.
+pub struct PlayerDied {
+    pub position: Vec3,
+}
+
+/// A block ran out of hit points. Its entity is already despawned.
+pub struct BlockDestroyed {
+    pub position: Vec3,
+}
+
+/// `shooter` fired `lasers` lasers at once from `origin`. Only sent so far, for audio
+/// and the like to pick up.
+#[allow(dead_code)]
+pub struct LaserFired {
+    pub shooter: Entity,
+    pub origin: Vec2,
+    pub lasers: u32,
+    pub from_player: bool,
+}
+
+/// A laser hit something and was despawned.
+pub struct LaserHit {
+    pub position: Vec3,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DespawnCause {
+    /// Its `Lifetime` ran out.
+    Expired,
+    /// It left its `DespawnOutside` bounds.
+    Outside,
+}
+
+/// An entity's `Lifetime` ran out or it left its bounds. It goes away, or back to its
+/// pool, at the end of the frame.
+pub struct Despawned {
+    pub entity: Entity,
+    pub cause: DespawnCause,
+}
+
+/// The player touched ground after being in the air.
+pub struct Landed {
+    pub position: Vec3,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JumpKind {
+    Ground,
+    Wall,
+    Air,
+}
+
+pub struct Jumped {
+    pub position: Vec3,
+    pub kind: JumpKind,
+}
+
+fn score_kills(
+    mut score: ResMut<Score>,
+    mut kills: ResMut<Kills>,
+    mut enemy_killed: EventReader<EnemyKilled>,
+) {
+    for killed in enemy_killed.iter() {
+        score.0 += killed.score;
+        kills.0 += 1;
+    }
+}
+
+/// Bosses are left out, they set off their own chain of explosions.
+fn explode_on_destroy(
+    mut commands: Commands,
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut player_died: EventReader<PlayerDied>,
+    mut block_destroyed: EventReader<BlockDestroyed>,
+) {
+    let positions = enemy_killed
+        .iter()
+        .filter(|killed| !killed.boss)
+        .map(|killed| killed.position)
+        .chain(player_died.iter().map(|died| died.position))
+        .chain(block_destroyed.iter().map(|destroyed| destroyed.position));
+    for position in positions {
+        commands.spawn().insert(ExplosionToSpawn(position));
+    }
+}
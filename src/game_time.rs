@@ -0,0 +1,134 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    console::{parse_arg, Console, ConsoleAppExt},
+    TIME_STEP,
+};
+
+pub struct GameTimePlugin;
+
+impl Plugin for GameTimePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(GameTime::default())
+            .add_console_command(
+                "timescale",
+                "timescale <scale>: slows down or speeds up the game",
+                console_timescale,
+            )
+            .add_console_command("pause", "pause: pauses or resumes the game", console_pause)
+            .add_console_command(
+                "step",
+                "step: advances a paused game one frame",
+                console_step,
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                game_time_update.system().after(InputSystem),
+            );
+    }
+}
+
+/// Game clock every gameplay system steps by instead of `TIME_STEP`, so the game can
+/// be slowed down, paused or stepped a frame at a time. P pauses, N steps.
+pub struct GameTime {
+    scale: f32,
+    paused: bool,
+    step: bool,
+    delta: f32,
+    elapsed: f64,
+}
+
+impl Default for GameTime {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            paused: false,
+            step: false,
+            delta: TIME_STEP,
+            elapsed: 0.,
+        }
+    }
+}
+
+impl GameTime {
+    /// Seconds the game advances this frame, 0 while paused.
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// How many normal frames `delta` is worth, for things applied once per frame.
+    pub fn frames(&self) -> f32 {
+        self.delta / TIME_STEP
+    }
+
+    /// Game seconds since startup, which don't pass while paused.
+    pub fn seconds(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Slow motion below 1, for bullet time as well as debugging.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Lets a paused game run one frame at normal speed.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+}
+
+fn game_time_update(
+    mut game_time: ResMut<GameTime>,
+    keyboard_input: Res<Input<KeyCode>>,
+    console: Res<Console>,
+) {
+    if !console.open {
+        if keyboard_input.just_pressed(KeyCode::P) {
+            game_time.paused = !game_time.paused;
+        }
+        if keyboard_input.just_pressed(KeyCode::N) {
+            game_time.step = true;
+        }
+    }
+
+    game_time.delta = if !game_time.paused {
+        TIME_STEP * game_time.scale
+    } else if game_time.step {
+        TIME_STEP
+    } else {
+        0.
+    };
+    game_time.step = false;
+    game_time.elapsed += game_time.delta as f64;
+}
+
+fn console_timescale(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let scale: f32 = parse_arg(args, 0, "scale")?;
+    let mut game_time = world.get_resource_mut::<GameTime>().ok_or("no game time")?;
+    game_time.set_scale(scale);
+    Ok(format!("time scale {}", game_time.scale))
+}
+
+fn console_pause(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    let mut game_time = world.get_resource_mut::<GameTime>().ok_or("no game time")?;
+    let paused = !game_time.paused();
+    game_time.set_paused(paused);
+    Ok(if paused { "paused" } else { "resumed" }.to_string())
+}
+
+fn console_step(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    let mut game_time = world.get_resource_mut::<GameTime>().ok_or("no game time")?;
+    if !game_time.paused() {
+        return Err("only works while paused".to_string());
+    }
+    game_time.step();
+    Ok("stepped".to_string())
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+
+use crate::{
+    collision::{layers, Collider, Contacts, Shape},
+    events::PlayerHit,
+    Hazard, Invulnerable, Materials, Player, WindowSize, GROUND_HEIGHT,
+};
+
+const SPIKES_HEIGHT: f32 = 15.;
+const LAVA_HEIGHT: f32 = 60.;
+const KILL_ZONE_MARGIN: f32 = 100.;
+const KILL_ZONE_THICKNESS: f32 = 100.;
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system_to_stage("game_setup_stage", kill_zone_spawn.system())
+            .add_system(spikes_fit.system().before("collision"))
+            .add_system(
+                player_hit_hazard
+                    .system()
+                    .after("collision")
+                    .before("player_hit"),
+            );
+    }
+}
+
+/// Puts a row of spikes on top of `block`. The spikes are a child of the block so they
+/// follow moving platforms and go away with destroyed blocks, `spikes_fit` sizes them.
+pub fn spawn_spikes(commands: &mut Commands, materials: &Materials, block: Entity) {
+    commands.entity(block).with_children(|parent| {
+        parent
+            .spawn_bundle(SpriteBundle {
+                material: materials.spikes.clone(),
+                sprite: Sprite {
+                    resize_mode: SpriteResizeMode::Manual,
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0., 0., 1.),
+                ..Default::default()
+            })
+            .insert(Hazard::Spikes)
+            .insert(hazard_collider());
+    });
+}
+
+/// Stretches spikes across the top of their block. Child transforms are in the block's
+/// unscaled space, so this reads the block's sprite size and undoes its scale for the
+/// `SPIKES_HEIGHT` they stick out.
+fn spikes_fit(
+    block_query: Query<(&Transform, &Sprite), Without<Hazard>>,
+    mut spikes_query: Query<(&Hazard, &Parent, &mut Sprite, &mut Transform)>,
+) {
+    for (hazard, parent, mut sprite, mut tf) in spikes_query.iter_mut() {
+        if !matches!(hazard, Hazard::Spikes) {
+            continue;
+        }
+        if let Ok((block_tf, block_sprite)) = block_query.get(parent.0) {
+            let height = SPIKES_HEIGHT / block_tf.scale.y;
+            sprite.size = Vec2::new(block_sprite.size.x, height);
+            tf.translation.y = block_sprite.size.y / 2. + height / 2.;
+        }
+    }
+}
+
+/// Replaces the ground with a lava strip along the bottom of the window.
+pub fn spawn_lava_floor(
+    commands: &mut Commands,
+    materials: &Materials,
+    win_size: &WindowSize,
+) -> Entity {
+    let ground_y = -win_size.height + GROUND_HEIGHT;
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.lava.clone(),
+            sprite: Sprite::new(Vec2::new(win_size.width, LAVA_HEIGHT)),
+            transform: Transform::from_xyz(0., ground_y, 5.),
+            ..Default::default()
+        })
+        .insert(Hazard::Lava)
+        .insert(hazard_collider())
+        .id()
+}
+
+/// Invisible kill planes just outside the left, right and bottom edges of the window.
+fn kill_zone_spawn(mut commands: Commands, win_size: Res<WindowSize>) {
+    let half_w = win_size.width / 2. + KILL_ZONE_MARGIN + KILL_ZONE_THICKNESS / 2.;
+    let half_h = win_size.height / 2. + KILL_ZONE_MARGIN + KILL_ZONE_THICKNESS / 2.;
+    let zones = [
+        (
+            Vec2::new(-half_w, 0.),
+            Vec2::new(KILL_ZONE_THICKNESS, win_size.height * 3.),
+        ),
+        (
+            Vec2::new(half_w, 0.),
+            Vec2::new(KILL_ZONE_THICKNESS, win_size.height * 3.),
+        ),
+        (
+            Vec2::new(0., -half_h),
+            Vec2::new(win_size.width * 3., KILL_ZONE_THICKNESS),
+        ),
+    ];
+    for (position, size) in zones.iter() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite::new(*size),
+                transform: Transform::from_translation(position.extend(0.)),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            })
+            .insert(Hazard::KillZone)
+            .insert(hazard_collider());
+    }
+}
+
+fn hazard_collider() -> Collider {
+    Collider::trigger(Shape::Sprite, layers::HAZARD, layers::PLAYER)
+}
+
+/// Checks every hazard the player touches, so standing in lava kills them as soon as
+/// their invulnerability wears off.
+fn player_hit_hazard(
+    contacts: Res<Contacts>,
+    mut player_hit: EventWriter<PlayerHit>,
+    player_query: Query<(Entity, &Transform, Option<&Invulnerable>), With<Player>>,
+    hazard_query: Query<&Hazard>,
+) {
+    if let Ok((player_entity, player_tf, invulnerable)) = player_query.single() {
+        for hazard_entity in contacts.of(player_entity) {
+            let hazard = match hazard_query.get(hazard_entity) {
+                Ok(hazard) => hazard,
+                Err(_) => continue,
+            };
+            if invulnerable.is_some() && !matches!(hazard, Hazard::KillZone) {
+                continue;
+            }
+            player_hit.send(PlayerHit {
+                player: player_entity,
+                position: player_tf.translation,
+            });
+            return;
+        }
+    }
+}
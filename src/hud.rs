@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+
+use crate::{
+    wave::WaveState, Boss, HitPoints, Player, PlayerAbilities, PlayerDash, PlayerState, WindowSize,
+    DASH_COOLDOWN,
+};
+
+const HUD_BAR_WIDTH: f32 = 120.;
+const HUD_BAR_HEIGHT: f32 = 10.;
+const HUD_MARGIN: f32 = 20.;
+const HUD_Z: f32 = 100.;
+const WAVE_PIP_SIZE: f32 = 8.;
+const WAVE_PIP_GAP: f32 = 4.;
+const WAVE_PIPS_PER_ROW: usize = 20;
+const BOSS_BAR_WIDTH: f32 = 500.;
+const BOSS_BAR_HEIGHT: f32 = 16.;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system_to_stage("game_setup_stage", hud_spawn.system())
+            .add_system(hud_dash_cooldown.system())
+            .add_system(hud_wave_counter.system())
+            .add_system(hud_boss_health.system());
+    }
+}
+
+struct HudMaterials {
+    wave_pip: Handle<ColorMaterial>,
+}
+
+/// Dash cooldown bar in the top left corner, hidden until dash is unlocked.
+struct DashBar;
+struct DashCooldownFill;
+/// One pip per wave reached, below the dash bar.
+struct WavePip;
+/// Boss health bar along the top of the window, hidden when there is no boss.
+struct BossHealthBar;
+struct BossHealthFill;
+
+fn hud_spawn(
+    mut commands: Commands,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    win_size: Res<WindowSize>,
+) {
+    let left = -win_size.width / 2. + HUD_MARGIN;
+    let top = win_size.height / 2. - HUD_MARGIN;
+
+    let hidden = Visible {
+        is_visible: false,
+        is_transparent: true,
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: color_materials.add(Color::rgba(1., 1., 1., 0.2).into()),
+            sprite: Sprite::new(Vec2::new(HUD_BAR_WIDTH, HUD_BAR_HEIGHT)),
+            transform: Transform::from_xyz(left + HUD_BAR_WIDTH / 2., top, HUD_Z),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(DashBar);
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: color_materials.add(Color::rgb(0.3, 0.8, 1.).into()),
+            sprite: Sprite::new(Vec2::new(HUD_BAR_WIDTH, HUD_BAR_HEIGHT)),
+            transform: Transform::from_xyz(left + HUD_BAR_WIDTH / 2., top, HUD_Z + 1.),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(DashBar)
+        .insert(DashCooldownFill);
+
+    let boss_bar_y = win_size.height / 2. - HUD_MARGIN;
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: color_materials.add(Color::rgba(1., 1., 1., 0.2).into()),
+            sprite: Sprite::new(Vec2::new(BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT)),
+            transform: Transform::from_xyz(0., boss_bar_y, HUD_Z),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(BossHealthBar);
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: color_materials.add(Color::rgb(0.9, 0.2, 0.2).into()),
+            sprite: Sprite::new(Vec2::new(BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT)),
+            transform: Transform::from_xyz(0., boss_bar_y, HUD_Z + 1.),
+            visible: hidden,
+            ..Default::default()
+        })
+        .insert(BossHealthBar)
+        .insert(BossHealthFill);
+
+    commands.insert_resource(HudMaterials {
+        wave_pip: color_materials.add(Color::rgb(1., 0.85, 0.3).into()),
+    });
+}
+
+/// Fills the dash bar from empty right after a dash to full once the dash is ready again.
+/// While the player is dead, shows whether the next one can dash.
+fn hud_dash_cooldown(
+    win_size: Res<WindowSize>,
+    player_state: Res<PlayerState>,
+    player_query: Query<(&PlayerDash, &PlayerAbilities), With<Player>>,
+    mut bar_query: Query<
+        (
+            &mut Sprite,
+            &mut Transform,
+            &mut Visible,
+            Option<&DashCooldownFill>,
+        ),
+        With<DashBar>,
+    >,
+) {
+    let (unlocked, ready) = match player_query.single() {
+        Ok((dash, abilities)) => (abilities.dash, 1. - dash.cooldown / DASH_COOLDOWN),
+        Err(_) => (player_state.abilities.dash, 1.),
+    };
+    let left = -win_size.width / 2. + HUD_MARGIN;
+    for (mut sprite, mut tf, mut visible, fill) in bar_query.iter_mut() {
+        visible.is_visible = unlocked;
+        if fill.is_some() {
+            sprite.size.x = HUD_BAR_WIDTH * ready;
+            tf.translation.x = left + sprite.size.x / 2.;
+        }
+    }
+}
+
+fn hud_wave_counter(
+    mut commands: Commands,
+    mut shown: Local<usize>,
+    wave_state: Res<WaveState>,
+    hud_materials: Res<HudMaterials>,
+    win_size: Res<WindowSize>,
+    pip_query: Query<Entity, With<WavePip>>,
+) {
+    let wave = wave_state.wave + 1;
+    if *shown == wave {
+        return;
+    }
+    *shown = wave;
+
+    for pip in pip_query.iter() {
+        commands.entity(pip).despawn();
+    }
+    let left = -win_size.width / 2. + HUD_MARGIN + WAVE_PIP_SIZE / 2.;
+    let top = win_size.height / 2. - HUD_MARGIN - HUD_BAR_HEIGHT - WAVE_PIP_SIZE;
+    for i in 0..wave {
+        let x = left + (i % WAVE_PIPS_PER_ROW) as f32 * (WAVE_PIP_SIZE + WAVE_PIP_GAP);
+        let y = top - (i / WAVE_PIPS_PER_ROW) as f32 * (WAVE_PIP_SIZE + WAVE_PIP_GAP);
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: hud_materials.wave_pip.clone(),
+                sprite: Sprite::new(Vec2::new(WAVE_PIP_SIZE, WAVE_PIP_SIZE)),
+                transform: Transform::from_xyz(x, y, HUD_Z),
+                ..Default::default()
+            })
+            .insert(WavePip);
+    }
+}
+
+fn hud_boss_health(
+    boss_query: Query<(&Boss, &HitPoints)>,
+    mut bar_query: Query<
+        (
+            &mut Sprite,
+            &mut Transform,
+            &mut Visible,
+            Option<&BossHealthFill>,
+        ),
+        With<BossHealthBar>,
+    >,
+) {
+    let health = boss_query
+        .iter()
+        .next()
+        .map(|(boss, hit_points)| hit_points.0 as f32 / boss.max_hit_points as f32);
+    for (mut sprite, mut tf, mut visible, fill) in bar_query.iter_mut() {
+        visible.is_visible = health.is_some();
+        if let (Some(health), Some(_)) = (health, fill) {
+            sprite.size.x = BOSS_BAR_WIDTH * health;
+            tf.translation.x = -BOSS_BAR_WIDTH / 2. + sprite.size.x / 2.;
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    block::{spawn_block, BlockKind},
+    console::ConsoleAppExt,
+    data::{load_ron, try_load_ron},
+    hazard::{spawn_lava_floor, spawn_spikes},
+    player::player_start,
+    LevelBlock, LevelFloor, Materials, Player, WindowSize, LEVEL_FILE,
+};
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(CurrentLevel(LEVEL_FILE.to_string()))
+            .add_console_command(
+                "level",
+                "level <file>: switches to a level file from the assets folder",
+                console_level,
+            )
+            .add_system(level_load.system());
+    }
+}
+
+/// Level file being played, relative to the assets folder. Changing it swaps the
+/// blocks and floor of the old level for the new one's.
+pub struct CurrentLevel(pub String);
+
+#[derive(Deserialize)]
+pub struct Level {
+    #[serde(default)]
+    pub floor: Floor,
+    pub blocks: Vec<LevelBlockSpec>,
+}
+
+#[derive(Deserialize, Default)]
+pub enum Floor {
+    #[default]
+    Ground,
+    Lava,
+}
+
+#[derive(Deserialize)]
+pub struct LevelBlockSpec {
+    pub x: f32,
+    pub y: f32,
+    pub kind: BlockKind,
+    #[serde(default)]
+    pub hit_points: Option<u32>,
+    #[serde(default)]
+    pub spikes: bool,
+}
+
+fn level_load(
+    mut commands: Commands,
+    current: Res<CurrentLevel>,
+    materials: Res<Materials>,
+    win_size: Res<WindowSize>,
+    level_query: Query<Entity, Or<(With<LevelBlock>, With<LevelFloor>)>>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    if !current.is_changed() {
+        return;
+    }
+    for entity in level_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // back to the start, so the player isn't left standing where the new level has lava
+    for mut tf in player_query.iter_mut() {
+        tf.translation = player_start(&win_size);
+    }
+
+    let level: Level = load_ron(&current.0);
+    if let Floor::Lava = level.floor {
+        let floor = spawn_lava_floor(&mut commands, &materials, &win_size);
+        commands.entity(floor).insert(LevelFloor);
+    }
+    for block in level.blocks.iter() {
+        let entity = spawn_block(
+            &mut commands,
+            &materials,
+            Vec2::new(block.x, block.y),
+            &block.kind,
+            block.hit_points,
+        );
+        commands.entity(entity).insert(LevelBlock);
+        if block.spikes {
+            spawn_spikes(&mut commands, &materials, entity);
+        }
+    }
+}
+
+fn console_level(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let file = *args.first().ok_or("missing level file")?;
+    // checked here, `level_load` treats a broken level as fatal
+    try_load_ron::<Level>(file)?;
+    let mut current = world.get_resource_mut::<CurrentLevel>().ok_or("no level")?;
+    current.0 = file.to_string();
+    Ok(format!("level {}", file))
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+
+use crate::{
+    events::{DespawnCause, Despawned},
+    game_time::GameTime,
+    WindowSize,
+};
+
+/// How far past the window edge things go before `DespawnOutside` removes them.
+pub const OFFSCREEN_MARGIN: f32 = 50.;
+
+pub struct LifetimePlugin;
+
+impl Plugin for LifetimePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(despawn_expired.system().label("lifetime"));
+    }
+}
+
+/// Seconds left before the entity goes away.
+pub struct Lifetime(pub f32);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Bounds {
+    /// Every side of the window.
+    Window,
+    /// Only the bottom of the window, for things that come in from above.
+    BelowWindow,
+}
+
+/// Removes the entity once its center is more than the margin outside the bounds.
+pub struct DespawnOutside(pub Bounds, pub f32);
+
+impl DespawnOutside {
+    pub fn window() -> Self {
+        Self(Bounds::Window, OFFSCREEN_MARGIN)
+    }
+
+    pub fn below_window() -> Self {
+        Self(Bounds::BelowWindow, OFFSCREEN_MARGIN)
+    }
+
+    fn outside(&self, position: Vec3, win_size: &WindowSize) -> bool {
+        let half_width = win_size.width / 2. + self.1;
+        let half_height = win_size.height / 2. + self.1;
+        match self.0 {
+            Bounds::Window => position.x.abs() > half_width || position.y.abs() > half_height,
+            Bounds::BelowWindow => position.y < -half_height,
+        }
+    }
+}
+
+/// Only sends `Despawned`, `EntityPools` releases the entity and whoever else cares,
+/// like `Population` and spawn telegraphs, reacts to it.
+fn despawn_expired(
+    game_time: Res<GameTime>,
+    win_size: Res<WindowSize>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&mut Lifetime>,
+            Option<&DespawnOutside>,
+        ),
+        Or<(With<Lifetime>, With<DespawnOutside>)>,
+    >,
+    mut despawned: EventWriter<Despawned>,
+) {
+    for (entity, tf, lifetime, outside) in query.iter_mut() {
+        let cause = if let Some(mut lifetime) = lifetime {
+            lifetime.0 -= game_time.delta();
+            if lifetime.0 <= 0. {
+                Some(DespawnCause::Expired)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        let cause = cause.or_else(|| {
+            outside
+                .filter(|outside| outside.outside(tf.translation, &win_size))
+                .map(|_| DespawnCause::Outside)
+        });
+        if let Some(cause) = cause {
+            despawned.send(Despawned { entity, cause });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn win_size() -> WindowSize {
+        WindowSize {
+            width: 800.,
+            height: 600.,
+        }
+    }
+
+    #[test]
+    fn window_bounds_include_the_margin_on_every_side() {
+        let outside = DespawnOutside(Bounds::Window, 50.);
+        assert!(!outside.outside(Vec3::new(449., 0., 0.), &win_size()));
+        assert!(outside.outside(Vec3::new(451., 0., 0.), &win_size()));
+        assert!(outside.outside(Vec3::new(-451., 0., 0.), &win_size()));
+        assert!(!outside.outside(Vec3::new(0., -349., 0.), &win_size()));
+        assert!(outside.outside(Vec3::new(0., 351., 0.), &win_size()));
+    }
+
+    #[test]
+    fn below_window_only_checks_the_bottom() {
+        let outside = DespawnOutside::below_window();
+        let margin = OFFSCREEN_MARGIN;
+        assert!(!outside.outside(Vec3::new(2000., 2000., 0.), &win_size()));
+        assert!(!outside.outside(Vec3::new(0., -300. - margin + 1., 0.), &win_size()));
+        assert!(outside.outside(Vec3::new(0., -300. - margin - 1., 0.), &win_size()));
+    }
+}
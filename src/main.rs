@@ -1,20 +1,76 @@
+// bevy systems take their resources and queries as parameters
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+mod animation;
 #[allow(unused)]
 mod block;
+mod boss;
+mod collision;
+mod console;
+mod data;
+mod debug;
+mod director;
 mod enemy;
+mod events;
+mod game_time;
+mod hazard;
+mod hud;
+mod level;
+mod lifetime;
+mod particles;
+mod pattern;
 mod player;
+mod pool;
+mod population;
+mod spatial;
+mod spawn;
+mod wave;
 
 use std::collections::HashSet;
 
-use bevy::{prelude::*, sprite::collide_aabb::collide, transform};
+use bevy::{
+    prelude::*,
+    sprite::collide_aabb::{collide, Collision},
+};
+use serde::Deserialize;
 
-use crate::{block::BlockPlugin, enemy::EnemyPlugin, player::PlayerPlugin};
+use crate::{
+    animation::{AnimationEnd, AnimationPlugin, SpriteAnimation},
+    block::BlockPlugin,
+    boss::BossPlugin,
+    collision::{layers, Collider, CollisionPlugin, CollisionStarted, Contacts},
+    console::ConsolePlugin,
+    debug::DebugPlugin,
+    director::DirectorPlugin,
+    enemy::{EnemyPlugin, EnemyTypes},
+    events::{BlockDestroyed, EnemyKilled, GameEventsPlugin, LaserHit, PlayerDied, PlayerHit},
+    game_time::{GameTime, GameTimePlugin},
+    hazard::HazardPlugin,
+    hud::HudPlugin,
+    level::LevelPlugin,
+    lifetime::LifetimePlugin,
+    particles::ParticlePlugin,
+    pattern::PatternPlugin,
+    player::PlayerPlugin,
+    pool::{release, EntityPools, PoolKind, PoolPlugin},
+    population::PopulationPlugin,
+    spawn::SpawnPlugin,
+    wave::WavePlugin,
+};
 
+const WINDOW_TITLE: &str = "test game";
 const PLAYER_SPRITE: &str = "player_a_01.png";
+const PLAYER_RUN_SPRITE: &str = "player_a_02.png";
+const PLAYER_AIR_SPRITE: &str = "player_b_01.png";
 const PLAYER_LASER_SPRITE: &str = "laser_a_01.png";
 const EXPLOSION_SHEET: &str = "explo_a_sheet.png";
-const ENEMY_SPRITE: &str = "enemy_a_02.png";
 const ENEMY_LASER_SPRITE: &str = "laser_b_01.png";
 const BLOCK_SPRITE: &str = "block.png";
+const LEVEL_FILE: &str = "levels/level_01.ron";
+const ENEMY_TYPES_FILE: &str = "enemies.ron";
+const WAVES_FILE: &str = "waves.ron";
+const BOSSES_FILE: &str = "bosses.ron";
+const PLAYER_FILE: &str = "player.ron";
 
 const TIME_STEP: f32 = 1. / 60.;
 const SCALE: f32 = 0.5;
@@ -22,14 +78,34 @@ const PLAYER_RESPAWM_DELAY: f64 = 2.;
 const GRAVITY_ACC: f32 = 30.;
 const GROUND_HEIGHT: f32 = 500.;
 const BLOCK_NUMBER: u32 = 10;
+const JUMP_SPEED: f32 = 1000.;
+const WALL_SLIDE_SPEED: f32 = 150.;
+const WALL_JUMP_PUSH: f32 = 600.;
+const PUSH_DECAY: f32 = 0.9;
+const DASH_SPEED: f32 = 1500.;
+const DASH_DURATION: f32 = 0.15;
+const DASH_COOLDOWN: f32 = 1.;
+const DASH_IFRAMES: f32 = 0.25;
+const AFTER_IMAGE_INTERVAL: f32 = 0.03;
+const AFTER_IMAGE_LIFETIME: f32 = 0.2;
+const DROP_THROUGH_TIME: f32 = 0.25;
+const CRUMBLE_DELAY: f32 = 0.5;
+const CRUMBLE_RESPAWN: f32 = 3.;
+/// Frames in the 4x4 `EXPLOSION_SHEET`.
+const EXPLOSION_FRAMES: u32 = 16;
+const EXPLOSION_FPS: f32 = 20.;
 // region : Resources
 pub struct Materials {
-    player: Handle<ColorMaterial>,
     player_laser: Handle<ColorMaterial>,
-    enemy: Handle<ColorMaterial>,
     enemy_laser: Handle<ColorMaterial>,
     explosion: Handle<TextureAtlas>,
     block: Handle<ColorMaterial>,
+    block_one_way: Handle<ColorMaterial>,
+    block_moving: Handle<ColorMaterial>,
+    block_crumbling: Handle<ColorMaterial>,
+    spikes: Handle<ColorMaterial>,
+    lava: Handle<ColorMaterial>,
+    telegraph: Handle<ColorMaterial>,
 }
 struct WindowSize {
     width: f32,
@@ -38,23 +114,25 @@ struct WindowSize {
 struct PlayerState {
     on: bool,
     last_shot: f64,
+    /// What the next player spawns with, kept from the last one.
+    abilities: PlayerAbilities,
 }
 
-struct BlockNumber(u32);
-
 impl Default for PlayerState {
     fn default() -> Self {
         Self {
             on: false,
             last_shot: 0.,
+            abilities: PlayerAbilities::default(),
         }
     }
 }
 
 impl PlayerState {
-    fn shot(&mut self, time: f64) {
+    fn shot(&mut self, time: f64, abilities: &PlayerAbilities) {
         self.on = false;
         self.last_shot = time;
+        self.abilities = abilities.clone();
     }
     fn spawned(&mut self) {
         self.on = true;
@@ -62,23 +140,185 @@ impl PlayerState {
     }
 }
 
-struct ActiveEnemies(u32);
+struct Score(u32);
+struct Kills(u32);
+/// Hits don't kill the player while on, toggled with the `god` console command.
+#[derive(Default)]
+struct GodMode(bool);
+/// Downward acceleration of the player each frame, `set gravity` changes it.
+struct Gravity(f32);
 //end region : Resources
 
 //region : Components
 struct Player;
+/// Where lasers can hit `player`. A separate entity so it can be smaller than the
+/// body the player stands on blocks with.
+struct PlayerHurtbox {
+    player: Entity,
+}
 struct PlayerReadyFire(bool);
 
+/// Named in `WAVES_FILE` rewards and by the `unlock` console command.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum Ability {
+    DoubleJump,
+    WallSlide,
+    WallJump,
+    Dash,
+}
+
+/// Movement abilities the player has unlocked. `PlayerState` holds on to them
+/// while the player is dead, so unlocks survive respawning.
+#[derive(Clone, Default)]
+struct PlayerAbilities {
+    air_jumps: u32,
+    wall_slide: bool,
+    wall_jump: bool,
+    dash: bool,
+}
+impl PlayerAbilities {
+    fn unlock(&mut self, ability: Ability) {
+        match ability {
+            Ability::DoubleJump => self.air_jumps += 1,
+            Ability::WallSlide => self.wall_slide = true,
+            Ability::WallJump => self.wall_jump = true,
+            Ability::Dash => self.dash = true,
+        }
+    }
+}
+
+/// Contact state written by `player_block_collide` and read by `player_movement` next frame.
+/// `wall` is -1. for a wall on the left, 1. for a wall on the right and 0. for none.
+struct PlayerMotion {
+    grounded: bool,
+    wall: f32,
+    facing: f32,
+    air_jumps_left: u32,
+    push_x: f32,
+    drop_through: f32,
+}
+impl Default for PlayerMotion {
+    fn default() -> Self {
+        Self {
+            grounded: false,
+            wall: 0.,
+            facing: 1.,
+            air_jumps_left: 0,
+            push_x: 0.,
+            drop_through: 0.,
+        }
+    }
+}
+
+/// Seconds left in the current dash, until the next dash and until the next after-image.
+#[derive(Default)]
+struct PlayerDash {
+    active: f32,
+    cooldown: f32,
+    trail: f32,
+}
+
+/// Seconds left during which enemy lasers and enemy contact are ignored.
+struct Invulnerable(f32);
+/// Fading copy of the dashing player's sprite, gone when its `Lifetime` runs out.
+struct AfterImage;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlayerAnimState {
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    Land,
+    /// Flashing after a hit the player survived, which only happens in god mode.
+    Hurt,
+}
+/// Which animation the player shows, picked by `player_animation` each frame.
+/// Dead players are despawned, a corpse sprite plays the dead animation instead.
+struct PlayerAnimation {
+    state: PlayerAnimState,
+    last_x: f32,
+    land_left: f32,
+    hurt_left: f32,
+}
+
 struct Laser;
 struct FromPlayer;
 
 struct FromEnemy;
 struct Enemy;
+/// Index of the enemy's type in `EnemyTypes`.
+struct EnemyKind(usize);
+/// Blinking marker where an enemy spawns once the marker's `Lifetime` runs out.
+struct SpawnTelegraph {
+    kind: usize,
+    patrol_block: Option<Entity>,
+}
+
+/// Index of the boss type in `BossTypes` and the phase it is in.
+struct Boss {
+    kind: usize,
+    max_hit_points: u32,
+    phase: usize,
+    since_fire: f32,
+}
+/// A defeated boss going off in a chain of explosions before it despawns.
+struct BossDying {
+    since_explosion: f32,
+    explosions_left: u32,
+}
+
+/// How an enemy moves, picked when it spawns.
+enum EnemyBehavior {
+    /// Falls straight down at `Speed.1`.
+    Fall,
+    /// Walks back and forth on top of `block`.
+    Patrol { block: Entity, direction: f32 },
+    /// Follows the player horizontally.
+    Chase,
+    /// Bobs around `origin` in a sine wave.
+    Hover { origin: Vec2, phase: f32 },
+    /// Dives at the player from `home`, then flies back.
+    Swoop { home: Vec2, state: SwoopState },
+}
+enum SwoopState {
+    Waiting(f32),
+    Diving(Vec2),
+    Retreating,
+}
 
 struct Explosion;
 struct ExplosionToSpawn(Vec3);
 
 struct Block;
+/// Platform that can be jumped up through and dropped down from with Down.
+struct OneWay;
+/// Platform looping along `path`; `delta` is how far it moved this frame so it can carry the player.
+struct MovingPlatform {
+    path: Vec<Vec2>,
+    speed: f32,
+    target: usize,
+    delta: Vec2,
+}
+/// Seconds left before a stood-on block breaks, or before a broken block comes back.
+enum Crumbling {
+    Solid,
+    Shaking(f32),
+    Broken(f32),
+}
+/// Blocks placed by a level file rather than by `block_spawn`.
+struct LevelBlock;
+/// The lava strip of a level whose floor is lava.
+struct LevelFloor;
+/// Hits left before the entity is destroyed; blocks without it are indestructible.
+struct HitPoints(u32);
+
+/// Kills the player on contact. Spikes and lava can be dashed over, kill zones can't.
+enum Hazard {
+    Spikes,
+    Lava,
+    KillZone,
+}
 struct Speed(f32, f32);
 impl Default for Speed {
     fn default() -> Self {
@@ -89,9 +329,6 @@ impl Speed {
     fn from_speed(speed: Vec2) -> Self {
         Self(speed.x, speed.y)
     }
-    fn reset_x(&mut self) {
-        self.0 = 0.;
-    }
     fn reset_y(&mut self) {
         self.1 = 0.;
     }
@@ -106,24 +343,53 @@ fn main() {
     App::build()
         .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
         .insert_resource(WindowDescriptor {
-            title: "test game".to_string(),
+            title: WINDOW_TITLE.to_string(),
             width: 1000.0,
             height: 1000.0,
             ..Default::default()
         })
-        .insert_resource(ActiveEnemies(0))
-        .insert_resource(BlockNumber(0))
+        .insert_resource(Score(0))
+        .insert_resource(Kills(0))
         .add_plugins(DefaultPlugins)
+        .add_plugin(GameEventsPlugin)
+        .add_plugin(GameTimePlugin)
+        .add_plugin(AnimationPlugin)
+        .add_plugin(PoolPlugin)
+        .add_plugin(LifetimePlugin)
+        .add_plugin(ParticlePlugin)
+        .add_plugin(CollisionPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(EnemyPlugin)
+        .add_plugin(WavePlugin)
+        .add_plugin(SpawnPlugin)
+        .add_plugin(PopulationPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(PatternPlugin)
+        .add_plugin(DirectorPlugin)
         .add_plugin(BlockPlugin)
+        .add_plugin(LevelPlugin)
+        .add_plugin(HazardPlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(DebugPlugin)
+        .add_plugin(ConsolePlugin)
         .add_startup_system(setup.system())
-        .add_system(player_hit_enemy.system())
-        .add_system(player_block_collide.system())
-        // .add_system(player_laser_hit_enemy.system())
-        // .add_system(enemy_laser_hit_player.system())
+        .add_system(player_hit_enemy.system().after("collision"))
+        .add_system(
+            player_block_collide
+                .system()
+                .label("player_block_collide")
+                .after("collision"),
+        )
+        .add_system(player_laser_hit_enemy.system().after("collision"))
+        .add_system(
+            enemy_laser_hit_player
+                .system()
+                .after("collision")
+                .before("player_hit"),
+        )
+        .add_system(player_hit.system().label("player_hit"))
+        .add_system(laser_hit_block.system().after("collision"))
         .add_system(explosion_to_spawn.system())
-        .add_system(animate_explosion.system())
         .run();
 }
 
@@ -140,14 +406,28 @@ fn setup(
 
     let texture_handle = asset_server.load(EXPLOSION_SHEET);
     let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(64., 64.), 4, 4);
+    let block_texture = asset_server.load(BLOCK_SPRITE);
 
     commands.insert_resource(Materials {
-        player: materials.add(asset_server.load(PLAYER_SPRITE).into()),
         player_laser: materials.add(asset_server.load(PLAYER_LASER_SPRITE).into()),
-        enemy: materials.add(asset_server.load(ENEMY_SPRITE).into()),
         enemy_laser: materials.add(asset_server.load(ENEMY_LASER_SPRITE).into()),
         explosion: texture_atlases.add(texture_atlas),
-        block: materials.add(asset_server.load(BLOCK_SPRITE).into()),
+        block: materials.add(block_texture.clone().into()),
+        block_one_way: materials.add(ColorMaterial::modulated_texture(
+            block_texture.clone(),
+            Color::rgb(0.5, 0.8, 1.),
+        )),
+        block_moving: materials.add(ColorMaterial::modulated_texture(
+            block_texture.clone(),
+            Color::rgb(0.6, 1., 0.6),
+        )),
+        block_crumbling: materials.add(ColorMaterial::modulated_texture(
+            block_texture,
+            Color::rgb(1., 0.7, 0.4),
+        )),
+        spikes: materials.add(Color::rgb(0.8, 0.8, 0.85).into()),
+        lava: materials.add(Color::rgb(1., 0.35, 0.).into()),
+        telegraph: materials.add(Color::rgba(1., 0.2, 0.2, 0.4).into()),
     });
     commands.insert_resource(WindowSize {
         width: window.width(),
@@ -156,81 +436,132 @@ fn setup(
     window.set_position(IVec2::new(0, 0));
 }
 
+/// The player rams through enemies they touch, as soon as they aren't invulnerable.
 fn player_hit_enemy(
     mut commands: Commands,
-    mut player_query: Query<(Entity, &Transform, &Sprite, With<Player>)>,
-    mut enemy_query: Query<(Entity, &Transform, &Sprite, With<Enemy>)>,
-    mut active_enemies: ResMut<ActiveEnemies>,
+    contacts: Res<Contacts>,
+    player_query: Query<Entity, (With<Player>, Without<Invulnerable>)>,
+    enemy_query: Query<(&Transform, &EnemyKind), With<Enemy>>,
+    enemy_types: Res<EnemyTypes>,
+    mut enemy_killed: EventWriter<EnemyKilled>,
 ) {
-    let mut enemies_blasted: HashSet<Entity> = HashSet::new();
-    if let Ok((player_entity, player_tf, player_sprite, _)) = player_query.single_mut() {
-        for (enemy_entity, enemy_tf, enemy_sprite, _) in enemy_query.iter_mut() {
-            let player_scale = Vec2::from(player_tf.scale);
-            let enemy_scale = Vec2::from(enemy_tf.scale);
-            let collision = collide(
-                player_tf.translation,
-                player_sprite.size * player_scale,
-                enemy_tf.translation,
-                enemy_sprite.size * enemy_scale,
-            );
+    if let Ok(player_entity) = player_query.single() {
+        for enemy_entity in contacts.of(player_entity) {
+            if let Ok((enemy_tf, enemy_kind)) = enemy_query.get(enemy_entity) {
+                commands.entity(enemy_entity).despawn();
+                // commands.entity(player_entity).despawn();
+                enemy_killed.send(EnemyKilled {
+                    position: enemy_tf.translation,
+                    score: enemy_types.get(enemy_kind).score,
+                    boss: false,
+                });
+            }
+        }
+    }
+}
 
-            if let Some(_) = collision {
-                if enemies_blasted.get(&enemy_entity).is_none() {
-                    commands.entity(enemy_entity).despawn();
-                    active_enemies.0 -= 1;
-                    // commands.entity(player_entity).despawn();
-                    commands
-                        .spawn()
-                        .insert(ExplosionToSpawn(enemy_tf.translation.clone()));
-                    enemies_blasted.insert(enemy_entity);
-                }
+fn player_laser_hit_enemy(
+    mut commands: Commands,
+    mut collision_started: EventReader<CollisionStarted>,
+    laser_query: Query<&Transform, (With<Laser>, With<FromPlayer>)>,
+    mut enemy_query: Query<(&Transform, &EnemyKind, &mut HitPoints), With<Enemy>>,
+    enemy_types: Res<EnemyTypes>,
+    mut enemy_killed: EventWriter<EnemyKilled>,
+    mut laser_hit: EventWriter<LaserHit>,
+) {
+    let mut lasers_spent: HashSet<Entity> = HashSet::new();
+    for CollisionStarted(pair) in collision_started.iter() {
+        let (laser_entity, enemy_entity) = match pair.between(layers::PLAYER_LASER, layers::ENEMY) {
+            Some(entities) => entities,
+            None => continue,
+        };
+        if lasers_spent.contains(&laser_entity) {
+            continue;
+        }
+        let laser_tf = match laser_query.get(laser_entity) {
+            Ok(laser_tf) => laser_tf,
+            Err(_) => continue,
+        };
+        if let Ok((enemy_tf, enemy_kind, mut hit_points)) = enemy_query.get_mut(enemy_entity) {
+            if hit_points.0 == 0 {
+                continue;
+            }
+            release(&mut commands, laser_entity);
+            lasers_spent.insert(laser_entity);
+            laser_hit.send(LaserHit {
+                position: laser_tf.translation,
+            });
+            hit_points.0 -= 1;
+            if hit_points.0 == 0 {
+                commands.entity(enemy_entity).despawn();
+                enemy_killed.send(EnemyKilled {
+                    position: enemy_tf.translation,
+                    score: enemy_types.get(enemy_kind).score,
+                    boss: false,
+                });
             }
         }
     }
 }
 
-// fn player_laser_hit_enemy(
-//     mut commands: Commands,
-//     mut laser_query: Query<(Entity, &Transform, &Sprite, (With<Laser>, With<FromPlayer>))>,
-//     mut enemy_query: Query<(Entity, &Transform, &Sprite, With<Enemy>)>,
-//     mut active_enemies: ResMut<ActiveEnemies>,
-// ) {
-//     let mut enemies_blasted: HashSet<Entity> = HashSet::new();
-//     for (laser_entity, laser_tf, laser_sprite, _) in laser_query.iter_mut() {
-//         for (enemy_entity, enemy_tf, enemy_sprite, _) in enemy_query.iter_mut() {
-//             let laser_scale = Vec2::from(laser_tf.scale);
-//             let enemy_scale = Vec2::from(enemy_tf.scale);
-//             let collision = collide(
-//                 laser_tf.translation,
-//                 laser_sprite.size * laser_scale,
-//                 enemy_tf.translation,
-//                 enemy_sprite.size * enemy_scale,
-//             );
-
-//             if let Some(_) = collision {
-//                 if enemies_blasted.get(&enemy_entity).is_none() {
-//                     commands.entity(enemy_entity).despawn();
-//                     active_enemies.0 -= 1;
-//                     commands.entity(laser_entity).despawn();
-
-//                     commands
-//                         .spawn()
-//                         .insert(ExplosionToSpawn(enemy_tf.translation.clone()));
-//                     enemies_blasted.insert(enemy_entity);
-//                 }
-//             }
-//         }
-//     }
-// }
+fn laser_hit_block(
+    mut commands: Commands,
+    mut collision_started: EventReader<CollisionStarted>,
+    laser_query: Query<&Transform, With<Laser>>,
+    mut block_query: Query<(&Transform, Option<&mut HitPoints>, Option<&Crumbling>), With<Block>>,
+    mut block_destroyed: EventWriter<BlockDestroyed>,
+    mut laser_hit: EventWriter<LaserHit>,
+) {
+    let mut lasers_spent: HashSet<Entity> = HashSet::new();
+    for CollisionStarted(pair) in collision_started.iter() {
+        let (laser_entity, block_entity) =
+            match pair.between(layers::PLAYER_LASER | layers::ENEMY_LASER, layers::BLOCK) {
+                Some(entities) => entities,
+                None => continue,
+            };
+        if lasers_spent.contains(&laser_entity) {
+            continue;
+        }
+        let laser_tf = match laser_query.get(laser_entity) {
+            Ok(laser_tf) => laser_tf,
+            Err(_) => continue,
+        };
+        if let Ok((block_tf, hit_points, crumbling)) = block_query.get_mut(block_entity) {
+            if let Some(Crumbling::Broken(_)) = crumbling {
+                continue;
+            }
+            // every block stops lasers, only blocks with hit points take damage
+            release(&mut commands, laser_entity);
+            lasers_spent.insert(laser_entity);
+            laser_hit.send(LaserHit {
+                position: laser_tf.translation,
+            });
+            if let Some(mut hit_points) = hit_points {
+                if hit_points.0 == 0 {
+                    continue;
+                }
+                hit_points.0 -= 1;
+                if hit_points.0 == 0 {
+                    commands.entity(block_entity).despawn_recursive();
+                    block_destroyed.send(BlockDestroyed {
+                        position: block_tf.translation,
+                    });
+                }
+            }
+        }
+    }
+}
 
 fn explosion_to_spawn(
     mut commands: Commands,
+    mut pools: ResMut<EntityPools>,
     query: Query<(Entity, &ExplosionToSpawn)>,
     materials: Res<Materials>,
 ) {
     for (explosion_spawn_entity, explosion_to_spawn) in query.iter() {
-        commands
-            .spawn_bundle(SpriteSheetBundle {
+        pools
+            .spawn(&mut commands, PoolKind::Explosion)
+            .insert_bundle(SpriteSheetBundle {
                 texture_atlas: materials.explosion.clone(),
                 transform: Transform {
                     translation: explosion_to_spawn.0,
@@ -239,103 +570,189 @@ fn explosion_to_spawn(
                 ..Default::default()
             })
             .insert(Explosion)
-            .insert(Timer::from_seconds(0.05, true));
+            .insert(SpriteAnimation::once(
+                0,
+                EXPLOSION_FRAMES - 1,
+                EXPLOSION_FPS,
+                AnimationEnd::Event,
+            ));
 
         commands.entity(explosion_spawn_entity).despawn();
     }
 }
 
-fn animate_explosion(
+/// Lasers fly through invulnerable players, and hit them if they still touch once
+/// that wears off.
+fn enemy_laser_hit_player(
     mut commands: Commands,
-    time: Res<Time>,
-    texture_atlases: Res<Assets<TextureAtlas>>,
-    mut query: Query<
-        (
-            Entity,
-            &mut Timer,
-            &mut TextureAtlasSprite,
-            &Handle<TextureAtlas>,
-        ),
-        With<Explosion>,
-    >,
+    contacts: Res<Contacts>,
+    hurtbox_query: Query<(Entity, &PlayerHurtbox)>,
+    player_query: Query<&Transform, (With<Player>, Without<Invulnerable>)>,
+    laser_query: Query<&Transform, (With<Laser>, With<FromEnemy>)>,
+    mut player_hit: EventWriter<PlayerHit>,
+    mut laser_hit: EventWriter<LaserHit>,
 ) {
-    for (entity, mut timer, mut sprite, texture_atlas_handle) in query.iter_mut() {
-        timer.tick(time.delta());
-        if timer.finished() {
-            let texture_atlas = texture_atlases.get(texture_atlas_handle).unwrap();
-            sprite.index += 1;
-            if sprite.index == texture_atlas.textures.len() as u32 {
-                commands.entity(entity).despawn()
+    for (hurtbox_entity, hurtbox) in hurtbox_query.iter() {
+        let player_tf = match player_query.get(hurtbox.player) {
+            Ok(player_tf) => player_tf,
+            Err(_) => continue,
+        };
+        for laser_entity in contacts.of(hurtbox_entity) {
+            if let Ok(laser_tf) = laser_query.get(laser_entity) {
+                release(&mut commands, laser_entity);
+                laser_hit.send(LaserHit {
+                    position: laser_tf.translation,
+                });
+                player_hit.send(PlayerHit {
+                    player: hurtbox.player,
+                    position: player_tf.translation,
+                });
             }
         }
     }
 }
 
-fn enemy_laser_hit_player(
+/// Any hit kills the player. Several hits in a frame only kill them once.
+fn player_hit(
+    game_time: Res<GameTime>,
     mut commands: Commands,
     mut player_state: ResMut<PlayerState>,
-    time: Res<Time>,
-    laser_query: Query<(Entity, &Transform, &Sprite), (With<Laser>, With<FromEnemy>)>,
-    player_query: Query<(Entity, &Transform, &Sprite), With<Player>>,
+    god_mode: Res<GodMode>,
+    mut player_hit: EventReader<PlayerHit>,
+    mut player_died: EventWriter<PlayerDied>,
+    player_query: Query<&PlayerAbilities, With<Player>>,
 ) {
-    if let Ok((player_entity, player_tf, player_sprite)) = player_query.single() {
-        let player_size = player_sprite.size * Vec2::from(player_tf.scale.abs());
-        for (laser_entity, laser_tf, laser_sprite) in laser_query.iter() {
-            let laser_size = laser_sprite.size * Vec2::from(laser_tf.scale.abs());
-            let collision = collide(
-                laser_tf.translation,
-                laser_size,
-                player_tf.translation,
-                player_size,
-            );
-
-            if let Some(_) = collision {
-                commands.entity(player_entity).despawn();
-                player_state.shot(time.seconds_since_startup());
-                commands.entity(laser_entity).despawn();
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(player_tf.translation.clone()));
-            }
+    let mut dead = HashSet::new();
+    for hit in player_hit.iter() {
+        if god_mode.0 || dead.contains(&hit.player) {
+            continue;
         }
+        let abilities = match player_query.get(hit.player) {
+            Ok(abilities) => abilities,
+            Err(_) => continue,
+        };
+        commands.entity(hit.player).despawn();
+        player_state.shot(game_time.seconds(), abilities);
+        player_died.send(PlayerDied {
+            position: hit.position,
+        });
+        dead.insert(hit.player);
     }
 }
 
+/// Pushes the player out of the solid blocks they touch.
 fn player_block_collide(
-    mut commands: Commands,
-    mut player_query: Query<(
-        &mut Speed,
-        &mut Transform,
-        &Sprite,
-        With<Player>,
-        Without<Block>,
-    )>,
-    mut player_state: ResMut<PlayerState>,
-    time: Res<Time>,
-    mut block_query: Query<(Entity, &Transform, &Sprite, With<Block>, Without<Player>)>,
+    game_time: Res<GameTime>,
+    contacts: Res<Contacts>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Speed,
+            &mut Transform,
+            &mut PlayerMotion,
+            &Collider,
+            Option<&Sprite>,
+        ),
+        (With<Player>, Without<Block>),
+    >,
+    mut block_query: Query<
+        (
+            &Transform,
+            &Collider,
+            Option<&Sprite>,
+            Option<&OneWay>,
+            Option<&MovingPlatform>,
+            Option<&mut Crumbling>,
+        ),
+        (With<Block>, Without<Player>),
+    >,
 ) {
-    if let Ok((mut player_speed, mut player_tf, player_sprite, _, _)) = player_query.single_mut() {
-        let player_size = player_sprite.size * Vec2::from(player_tf.scale.abs());
-        for (block_entity, block_tf, block_sprite, _, _) in block_query.iter_mut() {
-            let block_size = block_sprite.size * Vec2::from(block_tf.scale.abs());
+    if let Ok((
+        player_entity,
+        mut player_speed,
+        mut player_tf,
+        mut motion,
+        player_collider,
+        player_sprite,
+    )) = player_query.single_mut()
+    {
+        if player_collider.trigger {
+            return;
+        }
+        for block_entity in contacts.of(player_entity) {
+            let (block_tf, block_collider, block_sprite, one_way, moving, crumbling) =
+                match block_query.get_mut(block_entity) {
+                    Ok(block) => block,
+                    Err(_) => continue,
+                };
+            if block_collider.trigger {
+                continue;
+            }
+            if let Some(Crumbling::Broken(_)) = crumbling.as_deref() {
+                continue;
+            }
+            // earlier blocks may have moved the player, so the side is checked again here
+            let (player_center, player_size) = player_collider.bounds(&player_tf, player_sprite);
+            let (block_center, block_size) = block_collider.bounds(block_tf, block_sprite);
+            let player_offset = player_collider.offset;
             let collision = collide(
-                block_tf.translation,
-                block_size,
-                player_tf.translation,
+                player_center.extend(0.),
                 player_size,
+                block_center.extend(0.),
+                block_size,
             );
 
-            if let Some(_) = collision {
-                if (player_tf.translation.y > block_tf.translation.y) {
-                    player_tf.translation.y =
-                        block_tf.translation.y + block_size.y / 2. + player_size.y / 2.;
-                    player_speed.reset_y();
+            if one_way.is_some() {
+                // only land on one-way platforms when falling onto them from above
+                let block_top = block_center.y + block_size.y / 2.;
+                let last_bottom =
+                    player_center.y - player_size.y / 2. - player_speed.1 * game_time.delta();
+                let landing = matches!(collision, Some(Collision::Top))
+                    && player_speed.1 <= 0.
+                    && last_bottom >= block_top - 1.
+                    && motion.drop_through <= 0.;
+                if !landing {
+                    continue;
                 }
+            }
+
+            match collision {
+                Some(Collision::Top) => {
+                    player_tf.translation.y =
+                        block_center.y + block_size.y / 2. + player_size.y / 2. - player_offset.y;
+                    if player_speed.1 < 0. {
+                        player_speed.reset_y();
+                    }
+                    motion.grounded = true;
 
-                // commands.entity(block_entity).despawn();
-                // commands
-                //     .spawn()
-                //     .insert(ExplosionToSpawn(player_tf.translation.clone()));
+                    // the snap above already follows the platform up and down
+                    if let Some(platform) = moving {
+                        player_tf.translation.x += platform.delta.x;
+                    }
+                    if let Some(mut crumbling) = crumbling {
+                        if let Crumbling::Solid = *crumbling {
+                            *crumbling = Crumbling::Shaking(CRUMBLE_DELAY);
+                        }
+                    }
+                }
+                Some(Collision::Bottom) => {
+                    player_tf.translation.y =
+                        block_center.y - block_size.y / 2. - player_size.y / 2. - player_offset.y;
+                    if player_speed.1 > 0. {
+                        player_speed.reset_y();
+                    }
+                }
+                Some(Collision::Left) => {
+                    player_tf.translation.x =
+                        block_center.x - block_size.x / 2. - player_size.x / 2. - player_offset.x;
+                    motion.wall = 1.;
+                }
+                Some(Collision::Right) => {
+                    player_tf.translation.x =
+                        block_center.x + block_size.x / 2. + player_size.x / 2. - player_offset.x;
+                    motion.wall = -1.;
+                }
+                None => {}
             }
         }
     }
@@ -0,0 +1,456 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use crate::{
+    events::{BlockDestroyed, EnemyKilled, JumpKind, Jumped, Landed, LaserHit},
+    game_time::GameTime,
+    FromEnemy, Laser,
+};
+
+/// Colors each effect fades through, one material per step.
+const GRADIENT_STEPS: usize = 8;
+/// Particles alive or pooled at most, emitting past this drops particles.
+const MAX_PARTICLES: usize = 4000;
+const PARTICLE_Z: f32 = 20.;
+/// Particles per second behind each enemy laser.
+const TRAIL_RATE: f32 = 12.;
+/// From the player's position down to their feet, about half their hitbox.
+const PLAYER_FEET: f32 = 45.;
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(ParticlePool::default())
+            .add_startup_system(particle_effects_load.system())
+            .add_system(particle_effects.system())
+            .add_system(laser_trails.system())
+            .add_system(particle_emit.system().label("particle_emit"))
+            .add_system(particle_update.system().after("particle_emit"));
+    }
+}
+
+/// How particles of an effect look and move. Colors and sizes go from start to end
+/// over each particle's lifetime.
+#[derive(Clone)]
+pub struct EffectSpec {
+    /// Seconds, picked at random in this range for each particle.
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    /// Degrees either side of the emit direction.
+    pub spread: f32,
+    pub gravity: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+}
+
+/// An effect registered with `ParticleEffects::add`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EffectId(usize);
+
+/// Every effect particles can be emitted with, and the materials each one fades through.
+#[derive(Default)]
+pub struct ParticleEffects {
+    specs: Vec<EffectSpec>,
+    gradients: Vec<Vec<Handle<ColorMaterial>>>,
+}
+
+impl ParticleEffects {
+    pub fn add(
+        &mut self,
+        spec: EffectSpec,
+        color_materials: &mut Assets<ColorMaterial>,
+    ) -> EffectId {
+        let gradient = (0..GRADIENT_STEPS)
+            .map(|step| {
+                let t = step as f32 / (GRADIENT_STEPS - 1) as f32;
+                color_materials.add(lerp_color(spec.start_color, spec.end_color, t).into())
+            })
+            .collect();
+        self.specs.push(spec);
+        self.gradients.push(gradient);
+        EffectId(self.specs.len() - 1)
+    }
+
+    pub fn spec(&self, effect: EffectId) -> &EffectSpec {
+        &self.specs[effect.0]
+    }
+
+    fn gradient(&self, effect: EffectId) -> &[Handle<ColorMaterial>] {
+        &self.gradients[effect.0]
+    }
+}
+
+/// The effects the game itself emits.
+pub struct BuiltinEffects {
+    /// Kicked up sideways when landing.
+    pub dust: EffectId,
+    /// Pushed out under a jump.
+    pub puff: EffectId,
+    /// Laser impacts.
+    pub sparks: EffectId,
+    /// Enemy pieces flying off when it dies.
+    pub debris: EffectId,
+    /// Faint glow left behind moving lasers.
+    pub trail: EffectId,
+}
+
+fn particle_effects_load(
+    mut commands: Commands,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let mut effects = ParticleEffects::default();
+    let mut add = |spec| effects.add(spec, &mut color_materials);
+    let builtin = BuiltinEffects {
+        dust: add(EffectSpec {
+            lifetime: (0.3, 0.5),
+            speed: (60., 140.),
+            spread: 90.,
+            gravity: 200.,
+            start_color: Color::rgba(0.8, 0.75, 0.65, 0.8),
+            end_color: Color::rgba(0.8, 0.75, 0.65, 0.),
+            start_size: 6.,
+            end_size: 12.,
+        }),
+        puff: add(EffectSpec {
+            lifetime: (0.2, 0.35),
+            speed: (40., 100.),
+            spread: 50.,
+            gravity: 0.,
+            start_color: Color::rgba(1., 1., 1., 0.7),
+            end_color: Color::rgba(1., 1., 1., 0.),
+            start_size: 8.,
+            end_size: 14.,
+        }),
+        sparks: add(EffectSpec {
+            lifetime: (0.1, 0.25),
+            speed: (150., 350.),
+            spread: 180.,
+            gravity: 300.,
+            start_color: Color::rgb(1., 0.95, 0.6),
+            end_color: Color::rgba(1., 0.4, 0.1, 0.),
+            start_size: 4.,
+            end_size: 2.,
+        }),
+        debris: add(EffectSpec {
+            lifetime: (0.5, 0.9),
+            speed: (100., 300.),
+            spread: 180.,
+            gravity: 600.,
+            start_color: Color::rgb(0.7, 0.7, 0.75),
+            end_color: Color::rgba(0.3, 0.3, 0.35, 0.),
+            start_size: 7.,
+            end_size: 4.,
+        }),
+        trail: add(EffectSpec {
+            lifetime: (0.1, 0.2),
+            speed: (0., 20.),
+            spread: 180.,
+            gravity: 0.,
+            start_color: Color::rgba(1., 0.3, 0.3, 0.5),
+            end_color: Color::rgba(1., 0.3, 0.3, 0.),
+            start_size: 4.,
+            end_size: 1.,
+        }),
+    };
+    commands.insert_resource(effects);
+    commands.insert_resource(builtin);
+}
+
+pub enum EmitMode {
+    /// `count` particles at once, then the emitter is removed.
+    Burst(u32),
+    /// Particles per second for as long as the emitter exists.
+    Continuous(f32),
+}
+
+/// Emits particles from the entity's position. Burst emitters on entities that have
+/// nothing else are despawned with the emitter.
+pub struct ParticleEmitter {
+    pub effect: EffectId,
+    pub mode: EmitMode,
+    /// Center of the spread, straight up by default.
+    pub direction: Vec2,
+    since_emit: f32,
+}
+
+impl ParticleEmitter {
+    pub fn burst(effect: EffectId, count: u32, direction: Vec2) -> Self {
+        Self {
+            effect,
+            mode: EmitMode::Burst(count),
+            direction,
+            since_emit: 0.,
+        }
+    }
+
+    pub fn continuous(effect: EffectId, rate: f32) -> Self {
+        Self {
+            effect,
+            mode: EmitMode::Continuous(rate),
+            direction: Vec2::new(0., 1.),
+            since_emit: 0.,
+        }
+    }
+}
+
+/// Spawns a one-off burst at `position`.
+pub fn spawn_burst(
+    commands: &mut Commands,
+    effect: EffectId,
+    count: u32,
+    position: Vec3,
+    direction: Vec2,
+) {
+    commands
+        .spawn()
+        .insert(Transform::from_translation(position))
+        .insert(ParticleEmitter::burst(effect, count, direction));
+}
+
+struct Particle {
+    effect: EffectId,
+    active: bool,
+    velocity: Vec2,
+    gravity: f32,
+    age: f32,
+    lifetime: f32,
+    start_size: f32,
+    end_size: f32,
+    step: usize,
+}
+
+struct EmitRequest {
+    effect: EffectId,
+    position: Vec2,
+    direction: Vec2,
+}
+
+/// Particles are never despawned, dead ones are hidden and handed out again, so a
+/// burst costs component writes instead of entity spawns.
+#[derive(Default)]
+pub struct ParticlePool {
+    free: Vec<Entity>,
+    total: usize,
+    requests: Vec<EmitRequest>,
+}
+
+impl ParticlePool {
+    pub fn emit(&mut self, effect: EffectId, count: u32, position: Vec2, direction: Vec2) {
+        for _ in 0..count {
+            self.requests.push(EmitRequest {
+                effect,
+                position,
+                direction,
+            });
+        }
+    }
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    Color::rgba(
+        from.r() + (to.r() - from.r()) * t,
+        from.g() + (to.g() - from.g()) * t,
+        from.b() + (to.b() - from.b()) * t,
+        from.a() + (to.a() - from.a()) * t,
+    )
+}
+
+/// Dust on landing, puffs on jumping, sparks where lasers hit and debris from kills
+/// and destroyed blocks.
+fn particle_effects(
+    mut commands: Commands,
+    mut pool: ResMut<ParticlePool>,
+    effects: Res<BuiltinEffects>,
+    mut landed: EventReader<Landed>,
+    mut jumped: EventReader<Jumped>,
+    mut laser_hit: EventReader<LaserHit>,
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut block_destroyed: EventReader<BlockDestroyed>,
+) {
+    for landed in landed.iter() {
+        let feet = landed.position.truncate() - Vec2::new(0., PLAYER_FEET);
+        pool.emit(effects.dust, 4, feet, Vec2::new(1., 0.));
+        pool.emit(effects.dust, 4, feet, Vec2::new(-1., 0.));
+    }
+    for jumped in jumped.iter() {
+        let count = match jumped.kind {
+            JumpKind::Ground => 6,
+            JumpKind::Wall | JumpKind::Air => 4,
+        };
+        pool.emit(
+            effects.puff,
+            count,
+            jumped.position.truncate() - Vec2::new(0., PLAYER_FEET),
+            Vec2::new(0., -1.),
+        );
+    }
+    for hit in laser_hit.iter() {
+        pool.emit(
+            effects.sparks,
+            6,
+            hit.position.truncate(),
+            Vec2::new(0., 1.),
+        );
+    }
+    for killed in enemy_killed.iter() {
+        let count = if killed.boss { 40 } else { 12 };
+        pool.emit(
+            effects.debris,
+            count,
+            killed.position.truncate(),
+            Vec2::new(0., 1.),
+        );
+    }
+    for destroyed in block_destroyed.iter() {
+        spawn_burst(
+            &mut commands,
+            effects.debris,
+            16,
+            destroyed.position,
+            Vec2::new(0., 1.),
+        );
+    }
+}
+
+/// Gives new enemy lasers a trail.
+fn laser_trails(
+    mut commands: Commands,
+    effects: Res<BuiltinEffects>,
+    query: Query<Entity, (Added<Laser>, With<FromEnemy>, Without<ParticleEmitter>)>,
+) {
+    for laser in query.iter() {
+        commands
+            .entity(laser)
+            .insert(ParticleEmitter::continuous(effects.trail, TRAIL_RATE));
+    }
+}
+
+fn particle_emit(
+    mut commands: Commands,
+    game_time: Res<GameTime>,
+    mut pool: ResMut<ParticlePool>,
+    mut query: Query<(Entity, &mut ParticleEmitter, &Transform, Option<&Sprite>)>,
+) {
+    for (entity, mut emitter, tf, sprite) in query.iter_mut() {
+        let position = tf.translation.truncate();
+        let direction = emitter.direction;
+        match emitter.mode {
+            EmitMode::Burst(count) => {
+                pool.emit(emitter.effect, count, position, direction);
+                // bare emitters from `spawn_burst` go, emitters added to sprites only stop
+                if sprite.is_some() {
+                    commands.entity(entity).remove::<ParticleEmitter>();
+                } else {
+                    commands.entity(entity).despawn();
+                }
+            }
+            EmitMode::Continuous(rate) => {
+                if rate <= 0. {
+                    continue;
+                }
+                emitter.since_emit += game_time.delta();
+                let interval = 1. / rate;
+                let mut count = 0;
+                while emitter.since_emit >= interval {
+                    emitter.since_emit -= interval;
+                    count += 1;
+                }
+                pool.emit(emitter.effect, count, position, direction);
+            }
+        }
+    }
+}
+
+fn rotate(direction: Vec2, degrees: f32) -> Vec2 {
+    (Quat::from_rotation_z(degrees.to_radians()) * direction.extend(0.)).truncate()
+}
+
+/// Moves, fades and retires live particles, then starts the requested ones on pooled
+/// entities, spawning new ones only when the pool runs dry.
+fn particle_update(
+    mut commands: Commands,
+    game_time: Res<GameTime>,
+    effects: Res<ParticleEffects>,
+    mut pool: ResMut<ParticlePool>,
+    mut query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visible,
+        &mut Handle<ColorMaterial>,
+    )>,
+) {
+    let delta = game_time.delta();
+    let pool: &mut ParticlePool = &mut pool;
+    pool.free.clear();
+    for (entity, mut particle, mut tf, mut sprite, mut visible, mut material) in query.iter_mut() {
+        if particle.active {
+            particle.age += delta;
+            if particle.age < particle.lifetime {
+                particle.velocity.y -= particle.gravity * delta;
+                tf.translation += (particle.velocity * delta).extend(0.);
+
+                let t = particle.age / particle.lifetime;
+                sprite.size = Vec2::splat(
+                    particle.start_size + (particle.end_size - particle.start_size) * t,
+                );
+                let step = ((t * GRADIENT_STEPS as f32) as usize).min(GRADIENT_STEPS - 1);
+                if step != particle.step {
+                    particle.step = step;
+                    *material = effects.gradient(particle.effect)[step].clone();
+                }
+                continue;
+            }
+            particle.active = false;
+            visible.is_visible = false;
+        }
+        // retired this frame or earlier, free for the requests below
+        pool.free.push(entity);
+    }
+
+    let mut rng = thread_rng();
+    for request in pool.requests.drain(..) {
+        let spec = effects.spec(request.effect);
+        let velocity = rotate(request.direction, rng.gen_range(-spec.spread..=spec.spread))
+            * rng.gen_range(spec.speed.0..=spec.speed.1);
+        let particle = Particle {
+            effect: request.effect,
+            active: true,
+            velocity,
+            gravity: spec.gravity,
+            age: 0.,
+            lifetime: rng.gen_range(spec.lifetime.0..=spec.lifetime.1),
+            start_size: spec.start_size,
+            end_size: spec.end_size,
+            step: 0,
+        };
+        let first_color = effects.gradient(request.effect)[0].clone();
+        let translation = request.position.extend(PARTICLE_Z);
+
+        if let Some(entity) = pool.free.pop() {
+            if let Ok((_, mut pooled, mut tf, mut sprite, mut visible, mut material)) =
+                query.get_mut(entity)
+            {
+                *pooled = particle;
+                tf.translation = translation;
+                sprite.size = Vec2::splat(spec.start_size);
+                visible.is_visible = true;
+                *material = first_color;
+            }
+        } else if pool.total < MAX_PARTICLES {
+            pool.total += 1;
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: first_color,
+                    sprite: Sprite::new(Vec2::splat(spec.start_size)),
+                    transform: Transform::from_translation(translation),
+                    ..Default::default()
+                })
+                .insert(particle);
+        }
+    }
+}
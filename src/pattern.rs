@@ -0,0 +1,377 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    collision::{layers, Collider, Shape},
+    events::LaserFired,
+    game_time::GameTime,
+    lifetime::{DespawnOutside, Lifetime},
+    pool::{EntityPools, PoolKind},
+    FromEnemy, Laser, Materials, Player, Speed, SCALE,
+};
+
+const ENEMY_LASER_SPEED: f32 = 500.;
+/// Lasers start this far from the shooter's center along their direction.
+const MUZZLE_OFFSET: f32 = 15.;
+/// Slow lasers from dense patterns are cleared after this long even if still on screen.
+const ENEMY_LASER_LIFETIME: f32 = 10.;
+
+pub struct PatternPlugin;
+
+impl Plugin for PatternPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(burst_fire.system());
+    }
+}
+
+/// Enemy fire patterns, composable from the enemy and boss data files.
+/// Angles are in degrees, the base direction is straight down.
+#[derive(Deserialize, Clone)]
+pub enum FirePattern {
+    None,
+    /// One laser along the base direction.
+    Straight,
+    /// Turns the base direction of the inner pattern towards the player.
+    Aim(Box<FirePattern>),
+    /// `count` lasers fanned over `angle` degrees around the base direction.
+    Spread {
+        count: u32,
+        angle: f32,
+    },
+    /// `count` lasers evenly spaced all the way around.
+    Ring {
+        count: u32,
+    },
+    /// `arms` evenly spaced lasers, the whole star turning `turn` degrees per second.
+    Spiral {
+        arms: u32,
+        turn: f32,
+    },
+    /// Several patterns fired together.
+    Multi(Vec<FirePattern>),
+    /// Fires the inner pattern `shots` times, `interval` seconds apart.
+    /// Only repeats when it is the outermost pattern, nested bursts fire once.
+    Burst {
+        shots: u32,
+        interval: f32,
+        pattern: Box<FirePattern>,
+    },
+}
+
+/// Where a pattern is fired from and at.
+pub struct Shot {
+    pub origin: Vec2,
+    pub target: Option<Vec2>,
+    pub time: f32,
+}
+
+fn rotate(direction: Vec2, degrees: f32) -> Vec2 {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Vec2::new(
+        direction.x * cos - direction.y * sin,
+        direction.x * sin + direction.y * cos,
+    )
+}
+
+impl FirePattern {
+    /// Laser velocities for one shot of the pattern.
+    pub fn velocities(&self, direction: Vec2, shot: &Shot, out: &mut Vec<Vec2>) {
+        match self {
+            FirePattern::None => {}
+            FirePattern::Straight => out.push(direction * ENEMY_LASER_SPEED),
+            FirePattern::Aim(pattern) => {
+                let aimed = shot
+                    .target
+                    .map(|target| target - shot.origin)
+                    .filter(|to_target| to_target.length() > 0.)
+                    .map(|to_target| to_target.normalize())
+                    .unwrap_or(direction);
+                pattern.velocities(aimed, shot, out);
+            }
+            FirePattern::Spread { count, angle } => {
+                let count = (*count).max(1);
+                let step = if count > 1 {
+                    angle / (count - 1) as f32
+                } else {
+                    0.
+                };
+                let first = if count > 1 { -angle / 2. } else { 0. };
+                for i in 0..count {
+                    out.push(rotate(direction, first + step * i as f32) * ENEMY_LASER_SPEED);
+                }
+            }
+            FirePattern::Ring { count } => {
+                let count = (*count).max(1);
+                for i in 0..count {
+                    let degrees = 360. * i as f32 / count as f32;
+                    out.push(rotate(direction, degrees) * ENEMY_LASER_SPEED);
+                }
+            }
+            FirePattern::Spiral { arms, turn } => {
+                let arms = (*arms).max(1);
+                let offset = shot.time * turn;
+                for i in 0..arms {
+                    let degrees = offset + 360. * i as f32 / arms as f32;
+                    out.push(rotate(direction, degrees) * ENEMY_LASER_SPEED);
+                }
+            }
+            FirePattern::Multi(patterns) => {
+                for pattern in patterns.iter() {
+                    pattern.velocities(direction, shot, out);
+                }
+            }
+            FirePattern::Burst { pattern, .. } => pattern.velocities(direction, shot, out),
+        }
+    }
+}
+
+/// Repeats a burst pattern from its shooter until it runs out of shots or the shooter is gone.
+struct FireBurst {
+    shooter: Entity,
+    pattern: FirePattern,
+    shots_left: u32,
+    interval: f32,
+    since_shot: f32,
+}
+
+/// Fires one shot of `pattern` from `shooter`, bursts keep firing on their own afterwards.
+pub fn fire_pattern(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    materials: &Materials,
+    laser_fired: &mut EventWriter<LaserFired>,
+    pattern: &FirePattern,
+    shooter: Entity,
+    shot: &Shot,
+) {
+    if let FirePattern::Burst {
+        shots,
+        interval,
+        pattern,
+    } = pattern
+    {
+        if *shots > 1 {
+            commands.spawn().insert(FireBurst {
+                shooter,
+                pattern: (**pattern).clone(),
+                shots_left: shots - 1,
+                interval: *interval,
+                since_shot: 0.,
+            });
+        }
+    }
+    fire_once(
+        commands,
+        pools,
+        materials,
+        laser_fired,
+        pattern,
+        shooter,
+        shot,
+    );
+}
+
+fn fire_once(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    materials: &Materials,
+    laser_fired: &mut EventWriter<LaserFired>,
+    pattern: &FirePattern,
+    shooter: Entity,
+    shot: &Shot,
+) {
+    let mut velocities = Vec::new();
+    pattern.velocities(Vec2::new(0., -1.), shot, &mut velocities);
+    if velocities.is_empty() {
+        return;
+    }
+    for velocity in velocities.iter() {
+        spawn_enemy_laser(commands, pools, materials, shot.origin, *velocity);
+    }
+    laser_fired.send(LaserFired {
+        shooter,
+        origin: shot.origin,
+        lasers: velocities.len() as u32,
+        from_player: false,
+    });
+}
+
+/// Spawns an enemy laser moving at `velocity`, its sprite turned to face where it goes.
+pub fn spawn_enemy_laser(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    materials: &Materials,
+    origin: Vec2,
+    velocity: Vec2,
+) {
+    let direction = velocity.normalize();
+    let position = origin + direction * MUZZLE_OFFSET;
+    pools
+        .spawn(commands, PoolKind::EnemyLaser)
+        .insert_bundle(SpriteBundle {
+            material: materials.enemy_laser.clone(),
+            transform: Transform {
+                translation: position.extend(0.),
+                rotation: Quat::from_rotation_z((-direction.x).atan2(direction.y)),
+                scale: Vec3::new(SCALE, SCALE, 1.),
+            },
+            ..Default::default()
+        })
+        .insert(Laser)
+        .insert(FromEnemy)
+        .insert(Speed::from_speed(velocity))
+        .insert(Collider::trigger(
+            Shape::Sprite,
+            layers::ENEMY_LASER,
+            layers::PLAYER_HURTBOX | layers::BLOCK,
+        ))
+        .insert(Lifetime(ENEMY_LASER_LIFETIME))
+        .insert(DespawnOutside::window());
+}
+
+fn burst_fire(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut pools: ResMut<EntityPools>,
+    materials: Res<Materials>,
+    mut burst_query: Query<(Entity, &mut FireBurst)>,
+    shooter_query: Query<&Transform>,
+    player_query: Query<&Transform, With<Player>>,
+    mut laser_fired: EventWriter<LaserFired>,
+) {
+    let target = player_query
+        .single()
+        .ok()
+        .map(|tf| tf.translation.truncate());
+    for (entity, mut burst) in burst_query.iter_mut() {
+        let origin = match shooter_query.get(burst.shooter) {
+            Ok(tf) => tf.translation.truncate(),
+            Err(_) => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+        burst.since_shot += game_time.delta();
+        if burst.since_shot < burst.interval {
+            continue;
+        }
+        burst.since_shot = 0.;
+
+        let shot = Shot {
+            origin,
+            target,
+            time: game_time.seconds() as f32,
+        };
+        fire_once(
+            &mut commands,
+            &mut pools,
+            &materials,
+            &mut laser_fired,
+            &burst.pattern,
+            burst.shooter,
+            &shot,
+        );
+        burst.shots_left -= 1;
+        if burst.shots_left == 0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down() -> Vec2 {
+        Vec2::new(0., -1.)
+    }
+
+    fn shot(target: Option<Vec2>) -> Shot {
+        Shot {
+            origin: Vec2::ZERO,
+            target,
+            time: 0.,
+        }
+    }
+
+    fn velocities(pattern: &FirePattern, shot: &Shot) -> Vec<Vec2> {
+        let mut out = Vec::new();
+        pattern.velocities(down(), shot, &mut out);
+        out
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 0.01, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn straight_fires_down() {
+        let out = velocities(&FirePattern::Straight, &shot(None));
+        assert_eq!(out.len(), 1);
+        assert_close(out[0], down() * ENEMY_LASER_SPEED);
+    }
+
+    #[test]
+    fn spread_fans_around_the_base_direction() {
+        let pattern = FirePattern::Spread {
+            count: 3,
+            angle: 90.,
+        };
+        let out = velocities(&pattern, &shot(None));
+        assert_eq!(out.len(), 3);
+        assert_close(out[0], rotate(down(), -45.) * ENEMY_LASER_SPEED);
+        assert_close(out[1], down() * ENEMY_LASER_SPEED);
+        assert_close(out[2], rotate(down(), 45.) * ENEMY_LASER_SPEED);
+    }
+
+    #[test]
+    fn ring_is_evenly_spaced() {
+        let out = velocities(&FirePattern::Ring { count: 4 }, &shot(None));
+        assert_eq!(out.len(), 4);
+        assert_close(out.iter().fold(Vec2::ZERO, |sum, v| sum + *v), Vec2::ZERO);
+        for velocity in out.iter() {
+            assert!((velocity.length() - ENEMY_LASER_SPEED).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn spiral_turns_with_time() {
+        let pattern = FirePattern::Spiral { arms: 1, turn: 90. };
+        let later = Shot {
+            time: 1.,
+            ..shot(None)
+        };
+        let out = velocities(&pattern, &later);
+        assert_close(out[0], rotate(down(), 90.) * ENEMY_LASER_SPEED);
+    }
+
+    #[test]
+    fn aim_turns_towards_the_target() {
+        let pattern = FirePattern::Aim(Box::new(FirePattern::Straight));
+        let out = velocities(&pattern, &shot(Some(Vec2::new(10., 0.))));
+        assert_close(out[0], Vec2::new(ENEMY_LASER_SPEED, 0.));
+    }
+
+    #[test]
+    fn aim_without_a_target_keeps_the_base_direction() {
+        let pattern = FirePattern::Aim(Box::new(FirePattern::Straight));
+        let out = velocities(&pattern, &shot(Some(Vec2::ZERO)));
+        assert_close(out[0], down() * ENEMY_LASER_SPEED);
+        let out = velocities(&pattern, &shot(None));
+        assert_close(out[0], down() * ENEMY_LASER_SPEED);
+    }
+
+    #[test]
+    fn multi_and_burst_fire_their_inner_patterns_once() {
+        let pattern = FirePattern::Multi(vec![
+            FirePattern::Straight,
+            FirePattern::Burst {
+                shots: 3,
+                interval: 0.1,
+                pattern: Box::new(FirePattern::Ring { count: 2 }),
+            },
+            FirePattern::None,
+        ]);
+        assert_eq!(velocities(&pattern, &shot(None)).len(), 3);
+    }
+}
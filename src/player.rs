@@ -1,104 +1,292 @@
-use bevy::{core::FixedTimestep, ecs::system::Command, prelude::*, transform};
+use bevy::prelude::*;
+use serde::Deserialize;
 
 use crate::{
-    Block, FromPlayer, Laser, Materials, Player, PlayerReadyFire, PlayerState, Speed, WindowSize,
-    GRAVITY_ACC, GROUND_HEIGHT, PLAYER_RESPAWM_DELAY, TIME_STEP,
+    animation::{AnimationEnd, AnimationMaterials, AnimationMode, FitHeight, SpriteAnimation},
+    collision::{layers, Collider, Hitbox, Shape},
+    console::{parse_arg, ConsoleAppExt},
+    data::load_ron,
+    events::{JumpKind, Jumped, Landed, LaserFired, PlayerDied, PlayerHit},
+    game_time::GameTime,
+    lifetime::{DespawnOutside, Lifetime},
+    pool::{EntityPools, PoolKind},
+    Ability, AfterImage, Boss, FromEnemy, FromPlayer, GodMode, Gravity, Invulnerable, Laser,
+    Materials, Player, PlayerAbilities, PlayerAnimState, PlayerAnimation, PlayerDash,
+    PlayerHurtbox, PlayerMotion, PlayerReadyFire, PlayerState, Speed, WindowSize,
+    AFTER_IMAGE_INTERVAL, AFTER_IMAGE_LIFETIME, DASH_COOLDOWN, DASH_DURATION, DASH_IFRAMES,
+    DASH_SPEED, DROP_THROUGH_TIME, GRAVITY_ACC, GROUND_HEIGHT, JUMP_SPEED, PLAYER_AIR_SPRITE,
+    PLAYER_FILE, PLAYER_RESPAWM_DELAY, PLAYER_RUN_SPRITE, PLAYER_SPRITE, PUSH_DECAY,
+    WALL_JUMP_PUSH, WALL_SLIDE_SPEED,
 };
 
+/// Past this many enemy lasers on screen the player gets their bullet-heavy hurtbox.
+const BULLET_HEAVY_LASERS: usize = 40;
+/// On-screen height of every player frame, whatever the size of its image.
+const PLAYER_HEIGHT: f32 = 98.;
+const RUN_FPS: f32 = 8.;
+const LAND_TIME: f32 = 0.12;
+const HURT_TIME: f32 = 0.5;
+const HURT_FPS: f32 = 12.;
+/// Seconds the corpse of a dead player stays on screen.
+const DEAD_TIME: f32 = 1.;
+/// Moving less than this much sideways in a frame counts as standing still.
+const RUN_THRESHOLD: f32 = 0.5;
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.insert_resource(PlayerState::default())
+            .insert_resource(GodMode::default())
+            .insert_resource(Gravity(GRAVITY_ACC))
+            .add_console_command("god", "god: toggles invulnerability", console_god)
+            .add_console_command("tp", "tp <x> <y>: moves the player", console_tp)
+            .add_console_command(
+                "unlock",
+                "unlock <DoubleJump|WallSlide|WallJump|Dash>: gives the player an ability",
+                console_unlock,
+            )
+            .add_console_var("gravity", |world, value| {
+                if let Some(mut gravity) = world.get_resource_mut::<Gravity>() {
+                    gravity.0 = value;
+                }
+            })
+            .add_startup_system(player_spec_load.system())
+            .add_startup_system(player_animations_load.system())
             .add_startup_stage(
                 "game_setup_stage",
                 SystemStage::single(player_spawn.system()),
             )
-            .add_system(player_movement.system())
-            // .add_system(player_fire.system())
-            // .add_system(laser_movement.system())
-            .add_system_set(
-                SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(0.5))
-                    .with_system(player_spawn.system()),
-            );
+            .add_system(
+                player_movement
+                    .system()
+                    .label("player_movement")
+                    .after("console"),
+            )
+            .add_system(
+                player_hitbox_facing
+                    .system()
+                    .after("player_movement")
+                    .before("collision"),
+            )
+            .add_system(player_after_image.system().after("player_movement"))
+            .add_system(player_landing.system().after("player_block_collide"))
+            .add_system(
+                player_animation
+                    .system()
+                    .after("player_block_collide")
+                    .before("animation"),
+            )
+            .add_system(player_corpse.system())
+            .add_system(player_hurtbox.system().after("player_block_collide"))
+            .add_system(after_image_fade.system())
+            .add_system(player_invulnerability.system())
+            .add_system(player_fire.system().after("console"))
+            .add_system(laser_movement.system())
+            .add_system(player_spawn.system());
     }
 }
 
+/// Player collision shapes, from `PLAYER_FILE`.
+#[derive(Deserialize)]
+pub struct PlayerSpec {
+    /// Stands on blocks and rams enemies.
+    pub hitbox: Hitbox,
+    /// Where lasers hit, usually smaller than the body.
+    pub hurtbox: Hitbox,
+    /// Hurtbox used while a boss is around or the screen is full of lasers.
+    pub bullet_heavy_hurtbox: Hitbox,
+}
+
+fn player_spec_load(mut commands: Commands) {
+    let spec: PlayerSpec = load_ron(PLAYER_FILE);
+    commands.insert_resource(spec);
+}
+
+/// Respawns the player once `PLAYER_RESPAWM_DELAY` of game time has passed since they
+/// were shot, so the delay stops while paused and stretches while slowed down.
 fn player_spawn(
+    game_time: Res<GameTime>,
     mut commands: Commands,
-    materials: Res<Materials>,
-    windowSize: Res<WindowSize>,
-    time: Res<Time>,
+    animations: Res<PlayerAnimations>,
+    spec: Res<PlayerSpec>,
+    win_size: Res<WindowSize>,
     mut player_state: ResMut<PlayerState>,
 ) {
-    let now = time.seconds_since_startup();
+    let now = game_time.seconds();
     if !player_state.on
         && (player_state.last_shot == 0. || now > player_state.last_shot + PLAYER_RESPAWM_DELAY)
     {
-        let bottom = windowSize.height / 2. - 100.;
-        commands
+        let translation = player_start(&win_size);
+        let player = commands
             .spawn_bundle(SpriteBundle {
-                material: materials.player.clone(),
-                transform: Transform {
-                    translation: Vec3::new(0., bottom + 100., 10.),
-                    scale: Vec3::new(0.15, 0.15, 1.),
-                    ..Default::default()
-                },
+                material: animations.idle.clone(),
+                transform: Transform::from_translation(translation),
                 ..Default::default()
             })
+            .insert(FitHeight(PLAYER_HEIGHT))
             .insert(Player)
             .insert(PlayerReadyFire(true))
-            .insert(Speed::default());
+            .insert(Speed::default())
+            .insert(PlayerMotion::default())
+            .insert(PlayerDash::default())
+            .insert(player_state.abilities.clone())
+            .insert(PlayerAnimation {
+                state: PlayerAnimState::Idle,
+                last_x: translation.x,
+                land_left: 0.,
+                hurt_left: 0.,
+            })
+            .insert(animations.animation(PlayerAnimState::Idle))
+            .insert(animations.frames(PlayerAnimState::Idle))
+            .insert(spec.hitbox.collider(
+                false,
+                layers::PLAYER,
+                layers::ENEMY | layers::BLOCK | layers::HAZARD,
+            ))
+            .id();
+        commands
+            .spawn()
+            .insert(Transform::from_translation(translation))
+            .insert(
+                spec.hurtbox
+                    .collider(true, layers::PLAYER_HURTBOX, layers::ENEMY_LASER),
+            )
+            .insert(PlayerHurtbox { player });
 
         player_state.spawned();
     }
 }
 
 fn player_movement(
-    keyboardInput: Res<Input<KeyCode>>,
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
     win_size: Res<WindowSize>,
-    mut query: Query<(&mut Speed, &mut Transform, With<Player>)>,
+    gravity: Res<Gravity>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Speed,
+            &mut Transform,
+            &mut PlayerMotion,
+            &mut PlayerDash,
+            &PlayerAbilities,
+        ),
+        With<Player>,
+    >,
+    mut jumped: EventWriter<Jumped>,
 ) {
+    // nothing moves while paused, and keys pressed meanwhile aren't saved up for later
+    if game_time.delta() == 0. {
+        return;
+    }
     let ground_y = -win_size.height + GROUND_HEIGHT;
-    if let Ok((mut speed, mut transform, _)) = query.single_mut() {
+    if let Ok((entity, mut speed, mut transform, mut motion, mut dash, abilities)) =
+        query.single_mut()
+    {
         // x-dir
-        let x_direction = match &keyboardInput {
+        let x_direction = match &keyboard_input {
             x if x.pressed(KeyCode::Left) => -1.,
             x if x.pressed(KeyCode::Right) => 1.,
             _ => 0.,
         };
+        if x_direction != 0. {
+            motion.facing = x_direction;
+        }
+
+        // dash : a short horizontal burst in the facing direction, ignoring gravity
+        dash.cooldown = (dash.cooldown - game_time.delta()).max(0.);
+        if abilities.dash && dash.cooldown == 0. && keyboard_input.just_pressed(KeyCode::LShift) {
+            dash.active = DASH_DURATION;
+            dash.cooldown = DASH_COOLDOWN;
+            dash.trail = 0.;
+            commands.entity(entity).insert(Invulnerable(DASH_IFRAMES));
+        }
+        if dash.active > 0. {
+            dash.active -= game_time.delta();
+            speed.reset_y();
+            motion.grounded = false;
+            motion.wall = 0.;
+            transform.translation.x += motion.facing * DASH_SPEED * game_time.delta();
+            return;
+        }
+
+        if motion.grounded {
+            motion.air_jumps_left = abilities.air_jumps;
+        }
+
+        // drop down through one-way platforms
+        motion.drop_through = (motion.drop_through - game_time.delta()).max(0.);
+        if motion.grounded && keyboard_input.just_pressed(KeyCode::Down) {
+            motion.drop_through = DROP_THROUGH_TIME;
+        }
 
-        if keyboardInput.just_pressed(KeyCode::Space) && speed.1 == 0. {
-            speed.accelarate(Vec2::new(0., 1000.));
+        if keyboard_input.just_pressed(KeyCode::Space) {
+            let jump = if motion.grounded {
+                Some(JumpKind::Ground)
+            } else if abilities.wall_jump && motion.wall != 0. {
+                motion.push_x = -motion.wall * WALL_JUMP_PUSH;
+                Some(JumpKind::Wall)
+            } else if motion.air_jumps_left > 0 {
+                motion.air_jumps_left -= 1;
+                Some(JumpKind::Air)
+            } else {
+                None
+            };
+            if let Some(kind) = jump {
+                speed.reset_y();
+                speed.accelarate(Vec2::new(0., JUMP_SPEED));
+                jumped.send(Jumped {
+                    position: transform.translation,
+                    kind,
+                });
+            }
         }
 
         if transform.translation.y > ground_y {
-            speed.accelarate(Vec2::new(0., -GRAVITY_ACC));
+            speed.accelarate(Vec2::new(0., -gravity.0 * game_time.frames()));
+        }
+
+        // wall slide : pressing into a wall while falling caps the fall speed
+        if abilities.wall_slide
+            && !motion.grounded
+            && motion.wall != 0.
+            && x_direction == motion.wall
+            && speed.1 < -WALL_SLIDE_SPEED
+        {
+            speed.1 = -WALL_SLIDE_SPEED;
         }
 
-        if (transform.translation.y + speed.1 * TIME_STEP) < ground_y {
+        // contacts are re-detected by player_block_collide after this system
+        motion.grounded = false;
+        motion.wall = 0.;
+
+        if (transform.translation.y + speed.1 * game_time.delta()) < ground_y {
             transform.translation.y = ground_y;
             speed.reset_y();
+            motion.grounded = true;
         } else {
-            transform.translation.y += speed.1 * TIME_STEP;
+            transform.translation.y += speed.1 * game_time.delta();
         }
 
-        if (x_direction != 0.) {
-            transform.translation.x += x_direction * speed.0 * TIME_STEP;
+        transform.translation.x += (x_direction * speed.0 + motion.push_x) * game_time.delta();
+        motion.push_x *= PUSH_DECAY.powf(game_time.frames());
+        if motion.push_x.abs() < 1. {
+            motion.push_x = 0.;
         }
     }
     // if let Ok((speed, mut transform, _)) = query.single_mut() {
-    //     let x_direction = if keyboardInput.pressed(KeyCode::Left) {
+    //     let x_direction = if keyboard_input.pressed(KeyCode::Left) {
     //         -1.
-    //     } else if keyboardInput.pressed(KeyCode::Right) {
+    //     } else if keyboard_input.pressed(KeyCode::Right) {
     //         1.
     //     } else {
     //         0.
     //     };
-    // let y_direction = if keyboardInput.pressed(KeyCode::Down) {
+    // let y_direction = if keyboard_input.pressed(KeyCode::Down) {
     //     -1.
-    // } else if keyboardInput.pressed(KeyCode::Up) {
+    // } else if keyboard_input.pressed(KeyCode::Up) {
     //     1.
     // } else {
     //     0.
@@ -108,20 +296,304 @@ fn player_movement(
     // }
 }
 
+fn player_after_image(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(&mut PlayerDash, &Transform, &Sprite, &Handle<ColorMaterial>), With<Player>>,
+) {
+    if let Ok((mut dash, tf, sprite, material)) = query.single_mut() {
+        if dash.active <= 0. {
+            return;
+        }
+        dash.trail -= game_time.delta();
+        if dash.trail > 0. {
+            return;
+        }
+        dash.trail = AFTER_IMAGE_INTERVAL;
+
+        let texture = color_materials
+            .get(material)
+            .and_then(|material| material.texture.clone());
+        let mut after_image_tf = *tf;
+        after_image_tf.translation.z -= 1.;
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: color_materials.add(ColorMaterial {
+                    color: Color::rgba(1., 1., 1., 0.5),
+                    texture,
+                }),
+                sprite: Sprite {
+                    flip_x: sprite.flip_x,
+                    resize_mode: SpriteResizeMode::Manual,
+                    ..Sprite::new(sprite.size)
+                },
+                transform: after_image_tf,
+                ..Default::default()
+            })
+            .insert(AfterImage)
+            .insert(Lifetime(AFTER_IMAGE_LIFETIME));
+    }
+}
+
+fn after_image_fade(
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(&Lifetime, &Handle<ColorMaterial>), With<AfterImage>>,
+) {
+    for (lifetime, material) in query.iter() {
+        if let Some(material) = color_materials.get_mut(material) {
+            material
+                .color
+                .set_a(0.5 * (lifetime.0 / AFTER_IMAGE_LIFETIME).max(0.));
+        }
+    }
+}
+
+/// Sends `Landed` when the player touches ground after being in the air.
+fn player_landing(
+    mut was_grounded: Local<bool>,
+    query: Query<(&Transform, &PlayerMotion), With<Player>>,
+    mut landed: EventWriter<Landed>,
+) {
+    if let Ok((tf, motion)) = query.single() {
+        if motion.grounded && !*was_grounded {
+            landed.send(Landed {
+                position: tf.translation,
+            });
+        }
+        *was_grounded = motion.grounded;
+    }
+}
+
+/// Player sprites for each `PlayerAnimState`, tinted red ones for getting hit.
+struct PlayerAnimations {
+    idle: Handle<ColorMaterial>,
+    run: Handle<ColorMaterial>,
+    air: Handle<ColorMaterial>,
+    hurt: Handle<ColorMaterial>,
+    dead: Handle<ColorMaterial>,
+}
+
+impl PlayerAnimations {
+    fn animation(&self, state: PlayerAnimState) -> SpriteAnimation {
+        match state {
+            PlayerAnimState::Run => SpriteAnimation::new(0, 1, RUN_FPS, AnimationMode::PingPong),
+            PlayerAnimState::Land => {
+                SpriteAnimation::once(0, 1, 2. / LAND_TIME, AnimationEnd::Hold)
+            }
+            PlayerAnimState::Hurt => SpriteAnimation::new(0, 1, HURT_FPS, AnimationMode::Loop),
+            _ => SpriteAnimation::new(0, 0, 0., AnimationMode::Loop),
+        }
+    }
+
+    fn frames(&self, state: PlayerAnimState) -> AnimationMaterials {
+        let frames = match state {
+            PlayerAnimState::Idle => vec![self.idle.clone()],
+            PlayerAnimState::Run => vec![self.idle.clone(), self.run.clone()],
+            PlayerAnimState::Jump => vec![self.run.clone()],
+            PlayerAnimState::Fall => vec![self.air.clone()],
+            PlayerAnimState::Land => vec![self.air.clone(), self.idle.clone()],
+            PlayerAnimState::Hurt => vec![self.hurt.clone(), self.idle.clone()],
+        };
+        AnimationMaterials(frames)
+    }
+}
+
+fn player_animations_load(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let idle = asset_server.load(PLAYER_SPRITE);
+    let air = asset_server.load(PLAYER_AIR_SPRITE);
+    let hurt_tint = Color::rgb(1., 0.3, 0.3);
+    commands.insert_resource(PlayerAnimations {
+        idle: color_materials.add(idle.clone().into()),
+        run: color_materials.add(asset_server.load(PLAYER_RUN_SPRITE).into()),
+        air: color_materials.add(air.clone().into()),
+        hurt: color_materials.add(ColorMaterial::modulated_texture(idle, hurt_tint)),
+        dead: color_materials.add(ColorMaterial::modulated_texture(air, hurt_tint)),
+    });
+}
+
+/// Picks the player's animation from how they move, and flips the sprite to face
+/// where they're going.
+fn player_animation(
+    game_time: Res<GameTime>,
+    animations: Res<PlayerAnimations>,
+    mut landed: EventReader<Landed>,
+    mut player_hit: EventReader<PlayerHit>,
+    mut query: Query<
+        (
+            &mut PlayerAnimation,
+            &mut SpriteAnimation,
+            &mut AnimationMaterials,
+            &mut Handle<ColorMaterial>,
+            &mut Sprite,
+            &Transform,
+            &Speed,
+            &PlayerMotion,
+        ),
+        With<Player>,
+    >,
+) {
+    let landed = landed.iter().next().is_some();
+    let hit = player_hit.iter().next().is_some();
+    if let Ok((
+        mut player_animation,
+        mut animation,
+        mut frames,
+        mut material,
+        mut sprite,
+        tf,
+        speed,
+        motion,
+    )) = query.single_mut()
+    {
+        let delta = game_time.delta();
+        if landed {
+            player_animation.land_left = LAND_TIME;
+        }
+        if hit {
+            player_animation.hurt_left = HURT_TIME;
+        }
+        player_animation.land_left = (player_animation.land_left - delta).max(0.);
+        player_animation.hurt_left = (player_animation.hurt_left - delta).max(0.);
+
+        let moved_x = tf.translation.x - player_animation.last_x;
+        player_animation.last_x = tf.translation.x;
+        // paused frames keep whatever was showing
+        if delta == 0. {
+            return;
+        }
+
+        let state = if player_animation.hurt_left > 0. {
+            PlayerAnimState::Hurt
+        } else if !motion.grounded {
+            if speed.1 > 0. {
+                PlayerAnimState::Jump
+            } else {
+                PlayerAnimState::Fall
+            }
+        } else if player_animation.land_left > 0. {
+            PlayerAnimState::Land
+        } else if moved_x.abs() > RUN_THRESHOLD {
+            PlayerAnimState::Run
+        } else {
+            PlayerAnimState::Idle
+        };
+        if state != player_animation.state {
+            player_animation.state = state;
+            *animation = animations.animation(state);
+            *frames = animations.frames(state);
+            *material = frames.0[0].clone();
+        }
+        sprite.flip_x = motion.facing < 0.;
+    }
+}
+
+/// Leaves a corpse where the player died, playing the dead animation.
+fn player_corpse(
+    mut commands: Commands,
+    animations: Res<PlayerAnimations>,
+    mut player_died: EventReader<PlayerDied>,
+) {
+    for died in player_died.iter() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: animations.dead.clone(),
+                transform: Transform::from_translation(died.position),
+                ..Default::default()
+            })
+            .insert(FitHeight(PLAYER_HEIGHT))
+            .insert(SpriteAnimation::once(
+                0,
+                0,
+                1. / DEAD_TIME,
+                AnimationEnd::Despawn,
+            ));
+    }
+}
+
+/// Mirrors the body's hitbox along with the sprite.
+fn player_hitbox_facing(
+    spec: Res<PlayerSpec>,
+    mut query: Query<(&PlayerMotion, &mut Collider), With<Player>>,
+) {
+    for (motion, mut collider) in query.iter_mut() {
+        collider.offset = spec.hitbox.facing_offset(motion.facing);
+    }
+}
+
+/// Keeps hurtboxes where their player ends up each frame, mirrored with the sprite
+/// and shrunk in bullet-heavy moments, and removes them once the player is gone.
+fn player_hurtbox(
+    mut commands: Commands,
+    spec: Res<PlayerSpec>,
+    mut hurtbox_query: Query<
+        (Entity, &PlayerHurtbox, &mut Transform, &mut Collider),
+        Without<Player>,
+    >,
+    player_query: Query<(&Transform, &PlayerMotion), With<Player>>,
+    laser_query: Query<Entity, (With<Laser>, With<FromEnemy>)>,
+    boss_query: Query<Entity, With<Boss>>,
+) {
+    let bullet_heavy =
+        boss_query.iter().next().is_some() || laser_query.iter().count() >= BULLET_HEAVY_LASERS;
+    let hitbox = if bullet_heavy {
+        &spec.bullet_heavy_hurtbox
+    } else {
+        &spec.hurtbox
+    };
+    for (entity, hurtbox, mut tf, mut collider) in hurtbox_query.iter_mut() {
+        match player_query.get(hurtbox.player) {
+            Ok((player_tf, motion)) => {
+                tf.translation = player_tf.translation;
+                *collider = hitbox.collider(true, layers::PLAYER_HURTBOX, layers::ENEMY_LASER);
+                collider.offset = hitbox.facing_offset(motion.facing);
+            }
+            Err(_) => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+fn player_invulnerability(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        invulnerable.0 -= game_time.delta();
+        if invulnerable.0 <= 0. {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+/// Where the player spawns, and is put back to when the level changes.
+pub fn player_start(win_size: &WindowSize) -> Vec3 {
+    let bottom = win_size.height / 2. - 100.;
+    Vec3::new(0., bottom + 100., 10.)
+}
+
+/// Z fires two lasers straight up, once per key press. Space is taken by jumping.
 fn player_fire(
     mut commands: Commands,
+    mut pools: ResMut<EntityPools>,
     keyboard_input: Res<Input<KeyCode>>,
     materials: Res<Materials>,
-    mut query: Query<(&Transform, &mut PlayerReadyFire, With<Player>)>,
+    mut query: Query<(Entity, &Transform, &mut PlayerReadyFire, With<Player>)>,
+    mut laser_fired: EventWriter<LaserFired>,
 ) {
-    if let Ok((player_transform, mut player_ready_fire, _)) = query.single_mut() {
-        if player_ready_fire.0 && keyboard_input.pressed(KeyCode::Space) {
+    if let Ok((player_entity, player_transform, mut player_ready_fire, _)) = query.single_mut() {
+        if player_ready_fire.0 && keyboard_input.pressed(KeyCode::Z) {
             let x = player_transform.translation.x;
             let y = player_transform.translation.y;
 
             let mut spawn_lasers = |x_offset: f32| {
-                commands
-                    .spawn_bundle(SpriteBundle {
+                pools
+                    .spawn(&mut commands, PoolKind::PlayerLaser)
+                    .insert_bundle(SpriteBundle {
                         material: materials.player_laser.clone(),
                         transform: Transform {
                             translation: Vec3::new(x + x_offset, y, 0.),
@@ -131,29 +603,72 @@ fn player_fire(
                     })
                     .insert(Laser)
                     .insert(FromPlayer)
-                    .insert(Speed::default());
+                    .insert(Speed::default())
+                    .insert(Collider::trigger(
+                        Shape::Sprite,
+                        layers::PLAYER_LASER,
+                        layers::ENEMY | layers::BLOCK,
+                    ))
+                    .insert(DespawnOutside::window());
                 player_ready_fire.0 = false;
             };
             let x_offset = 20.;
             spawn_lasers(x_offset);
             spawn_lasers(-x_offset);
+            laser_fired.send(LaserFired {
+                shooter: player_entity,
+                origin: Vec2::new(x, y),
+                lasers: 2,
+                from_player: true,
+            });
         }
-        if keyboard_input.just_released(KeyCode::Space) {
+        if keyboard_input.just_released(KeyCode::Z) {
             player_ready_fire.0 = true;
         }
     }
 }
 
 fn laser_movement(
-    mut commands: Commands,
-    win_size: Res<WindowSize>,
-    mut query: Query<(Entity, &Speed, &mut Transform), (With<Laser>, With<FromPlayer>)>,
+    game_time: Res<GameTime>,
+    mut query: Query<(&Speed, &mut Transform), (With<Laser>, With<FromPlayer>)>,
 ) {
-    for (laser_entity, speed, mut laser_transform) in query.iter_mut() {
-        let translation = &mut laser_transform.translation;
-        translation.y += speed.0 * TIME_STEP;
-        if (translation.y > win_size.height) {
-            commands.entity(laser_entity).despawn();
-        }
+    for (speed, mut laser_transform) in query.iter_mut() {
+        laser_transform.translation.y += speed.0 * game_time.delta();
     }
 }
+
+fn console_god(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    let mut god_mode = world.get_resource_mut::<GodMode>().ok_or("no god mode")?;
+    god_mode.0 = !god_mode.0;
+    Ok(format!(
+        "god mode {}",
+        if god_mode.0 { "on" } else { "off" }
+    ))
+}
+
+fn console_unlock(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name = *args.first().ok_or("missing ability")?;
+    let ability: Ability =
+        ron::from_str(name).map_err(|_| format!("`{}` is not an ability", name))?;
+    let mut query = world.query_filtered::<&mut PlayerAbilities, With<Player>>();
+    match query.iter_mut(world).next() {
+        Some(mut abilities) => abilities.unlock(ability),
+        // dead, the next player gets it
+        None => world
+            .get_resource_mut::<PlayerState>()
+            .ok_or("no player state")?
+            .abilities
+            .unlock(ability),
+    }
+    Ok(format!("unlocked {:?}", ability))
+}
+
+fn console_tp(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let x: f32 = parse_arg(args, 0, "x")?;
+    let y: f32 = parse_arg(args, 1, "y")?;
+    let mut query = world.query_filtered::<&mut Transform, With<Player>>();
+    let mut tf = query.iter_mut(world).next().ok_or("no player")?;
+    tf.translation.x = x;
+    tf.translation.y = y;
+    Ok(format!("player at {} {}", x, y))
+}
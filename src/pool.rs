@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::system::{Command, EntityCommands},
+    prelude::*,
+};
+
+use crate::{
+    animation::{AnimationFinished, SpriteAnimation},
+    collision::Collider,
+    events::Despawned,
+    lifetime::{DespawnOutside, Lifetime},
+    particles::ParticleEmitter,
+    Explosion, FromEnemy, FromPlayer, Laser, Speed,
+};
+
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(EntityPools::default())
+            .add_system(release_finished.system().after("animation"))
+            .add_system(release_despawned.system().after("lifetime"));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PoolKind {
+    PlayerLaser,
+    EnemyLaser,
+    Explosion,
+}
+
+/// On entities handed out by `EntityPools`, so `release` knows which pool they go back to.
+pub struct Pooled(pub PoolKind);
+
+/// Hidden and stripped of its gameplay components until it is handed out again.
+pub struct InPool;
+
+/// Entities that are hidden instead of despawned, so bullet-heavy patterns reuse them
+/// instead of spawning and despawning thousands a second.
+#[derive(Default)]
+pub struct EntityPools {
+    free: HashMap<PoolKind, Vec<Entity>>,
+}
+
+impl EntityPools {
+    /// A pooled entity when there is one, a new one otherwise. Either way, set it up
+    /// like a freshly spawned entity, starting with its bundle.
+    pub fn spawn<'a, 'b>(
+        &mut self,
+        commands: &'b mut Commands<'a>,
+        kind: PoolKind,
+    ) -> EntityCommands<'a, 'b> {
+        let entity = match self.free.get_mut(&kind).and_then(|free| free.pop()) {
+            Some(entity) => {
+                commands.entity(entity).remove::<InPool>();
+                entity
+            }
+            None => commands.spawn().id(),
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(Pooled(kind));
+        entity_commands
+    }
+
+    pub fn free(&self, kind: PoolKind) -> usize {
+        self.free.get(&kind).map_or(0, |free| free.len())
+    }
+}
+
+/// Puts a pooled entity back in its pool, entities that didn't come from one are despawned.
+pub fn release(commands: &mut Commands, entity: Entity) {
+    commands.add(Release(entity));
+}
+
+struct Release(Entity);
+
+impl Command for Release {
+    fn write(self: Box<Self>, world: &mut World) {
+        let kind = match world.get_entity(self.0) {
+            // released twice in a frame, or despawned meanwhile
+            None => return,
+            Some(entity) if entity.contains::<InPool>() => return,
+            Some(entity) => entity.get::<Pooled>().map(|pooled| pooled.0),
+        };
+        let kind = match kind {
+            Some(kind) => kind,
+            None => {
+                world.despawn(self.0);
+                return;
+            }
+        };
+
+        let mut entity = world.entity_mut(self.0);
+        entity.remove_bundle_intersection::<(
+            Laser,
+            FromPlayer,
+            FromEnemy,
+            Speed,
+            Collider,
+            ParticleEmitter,
+            Explosion,
+            SpriteAnimation,
+            Lifetime,
+            DespawnOutside,
+        )>();
+        entity.insert(InPool);
+        if let Some(mut visible) = entity.get_mut::<Visible>() {
+            visible.is_visible = false;
+        }
+        world
+            .get_resource_or_insert_with(EntityPools::default)
+            .free
+            .entry(kind)
+            .or_default()
+            .push(self.0);
+    }
+}
+
+/// Pooled effects end their animation with an event instead of despawning.
+fn release_finished(
+    mut commands: Commands,
+    mut animation_finished: EventReader<AnimationFinished>,
+    pooled_query: Query<(), With<Pooled>>,
+) {
+    for finished in animation_finished.iter() {
+        if pooled_query.get(finished.entity).is_ok() {
+            release(&mut commands, finished.entity);
+        }
+    }
+}
+
+/// Releases whatever `despawn_expired` is done with, pooled or not.
+fn release_despawned(mut commands: Commands, mut despawned: EventReader<Despawned>) {
+    for despawned in despawned.iter() {
+        release(&mut commands, despawned.entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+
+    fn pooled_laser(world: &mut World) -> Entity {
+        world
+            .spawn()
+            .insert(Pooled(PoolKind::EnemyLaser))
+            .insert(Laser)
+            .insert(FromEnemy)
+            .insert(Speed(0., -100.))
+            .insert(Visible::default())
+            .id()
+    }
+
+    fn release_now(world: &mut World, entity: Entity) {
+        Box::new(Release(entity)).write(world);
+    }
+
+    #[test]
+    fn release_strips_hides_and_frees_pooled_entities() {
+        let mut world = World::new();
+        let laser = pooled_laser(&mut world);
+        release_now(&mut world, laser);
+
+        assert!(world.get::<Laser>(laser).is_none());
+        assert!(world.get::<Speed>(laser).is_none());
+        assert!(world.get::<InPool>(laser).is_some());
+        assert!(!world.get::<Visible>(laser).unwrap().is_visible);
+        let pools = world.get_resource::<EntityPools>().unwrap();
+        assert_eq!(pools.free(PoolKind::EnemyLaser), 1);
+        assert_eq!(pools.free(PoolKind::PlayerLaser), 0);
+    }
+
+    #[test]
+    fn releasing_twice_frees_once() {
+        let mut world = World::new();
+        let laser = pooled_laser(&mut world);
+        release_now(&mut world, laser);
+        release_now(&mut world, laser);
+        let pools = world.get_resource::<EntityPools>().unwrap();
+        assert_eq!(pools.free(PoolKind::EnemyLaser), 1);
+    }
+
+    #[test]
+    fn entities_without_a_pool_are_despawned() {
+        let mut world = World::new();
+        let entity = world.spawn().insert(Laser).id();
+        release_now(&mut world, entity);
+        assert!(world.get_entity(entity).is_none());
+        release_now(&mut world, entity);
+    }
+
+    #[test]
+    fn spawn_hands_released_entities_out_again() {
+        let mut world = World::new();
+        let laser = pooled_laser(&mut world);
+        release_now(&mut world, laser);
+
+        let mut pools = world.remove_resource::<EntityPools>().unwrap();
+        let mut queue = CommandQueue::default();
+        let (reused, fresh) = {
+            let mut commands = Commands::new(&mut queue, &world);
+            let reused = pools.spawn(&mut commands, PoolKind::EnemyLaser).id();
+            let fresh = pools.spawn(&mut commands, PoolKind::EnemyLaser).id();
+            (reused, fresh)
+        };
+        queue.apply(&mut world);
+
+        assert_eq!(reused, laser);
+        assert_ne!(fresh, laser);
+        assert_eq!(pools.free(PoolKind::EnemyLaser), 0);
+        assert!(world.get::<InPool>(reused).is_none());
+        assert!(world.get::<Pooled>(fresh).is_some());
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{events::Despawned, Block, EnemyKind, LevelBlock, SpawnTelegraph};
+
+pub struct PopulationPlugin;
+
+impl Plugin for PopulationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // removals are only visible in the frame they happen, so this runs after
+        // the update stage has applied its despawns
+        app.insert_resource(Population::default())
+            .add_system(population_despawned.system().after("lifetime"))
+            .add_system_to_stage(CoreStage::PostUpdate, population_track.system());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tracked {
+    /// An enemy of the given type, or a telegraph about to become one.
+    Enemy(usize),
+    /// A block from `block_spawn`, level blocks aren't tracked.
+    Block,
+}
+
+/// Live enemies and spawned blocks, keyed by entity so an entity is never counted
+/// twice however many systems spawn or despawn it.
+#[derive(Default)]
+pub struct Population {
+    tracked: HashMap<Entity, Tracked>,
+}
+
+impl Population {
+    /// Counts `entity` right away, without waiting for `population_track` to see it.
+    pub fn track(&mut self, entity: Entity, tracked: Tracked) {
+        self.tracked.insert(entity, tracked);
+    }
+
+    /// Enemies and spawn telegraphs, bosses excluded.
+    pub fn enemies(&self) -> u32 {
+        self.tracked
+            .values()
+            .filter(|tracked| matches!(tracked, Tracked::Enemy(_)))
+            .count() as u32
+    }
+
+    pub fn enemies_of_kind(&self, kind: usize) -> u32 {
+        self.tracked
+            .values()
+            .filter(|&&tracked| tracked == Tracked::Enemy(kind))
+            .count() as u32
+    }
+
+    pub fn blocks(&self) -> u32 {
+        self.tracked
+            .values()
+            .filter(|&&tracked| tracked == Tracked::Block)
+            .count() as u32
+    }
+}
+
+/// Stops counting enemies that fell out of the window right away, so spawning later
+/// this frame already has room for new ones.
+fn population_despawned(mut population: ResMut<Population>, mut despawned: EventReader<Despawned>) {
+    for despawned in despawned.iter() {
+        population.tracked.remove(&despawned.entity);
+    }
+}
+
+fn population_track(
+    mut population: ResMut<Population>,
+    enemy_query: Query<(Entity, &EnemyKind), Added<EnemyKind>>,
+    telegraph_query: Query<(Entity, &SpawnTelegraph), Added<SpawnTelegraph>>,
+    block_query: Query<Entity, (Added<Block>, Without<LevelBlock>)>,
+    removed_enemies: RemovedComponents<EnemyKind>,
+    removed_telegraphs: RemovedComponents<SpawnTelegraph>,
+    removed_blocks: RemovedComponents<Block>,
+) {
+    for entity in removed_enemies
+        .iter()
+        .chain(removed_telegraphs.iter())
+        .chain(removed_blocks.iter())
+    {
+        population.tracked.remove(&entity);
+    }
+
+    for (entity, kind) in enemy_query.iter() {
+        population.track(entity, Tracked::Enemy(kind.0));
+    }
+    for (entity, telegraph) in telegraph_query.iter() {
+        population.track(entity, Tracked::Enemy(telegraph.kind));
+    }
+    for entity in block_query.iter() {
+        population.track(entity, Tracked::Block);
+    }
+}
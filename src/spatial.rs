@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use bevy::math::Vec2;
+
+/// Uniform grid bucketing boxes by the cells they cover, so only boxes sharing a cell
+/// need a narrow phase check instead of every pair. Boxes carry layer and mask bits
+/// like colliders do, pairs that can't interact are skipped before the narrow phase.
+///
+/// Kept free of anything game specific so `benches/collision.rs` can include it.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>, BuildHasherDefault<CellHasher>>,
+    boxes: Vec<Entry>,
+}
+
+struct Entry {
+    /// Lowest cell the box covers.
+    min_cell: (i32, i32),
+    layer: u32,
+    mask: u32,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            boxes: Vec::new(),
+        }
+    }
+
+    fn cell(&self, point: Vec2) -> (i32, i32) {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Adds a box and returns its index, boxes are numbered in insertion order.
+    pub fn insert(&mut self, center: Vec2, size: Vec2, layer: u32, mask: u32) -> usize {
+        let index = self.boxes.len();
+        let min = self.cell(center - size / 2.);
+        let max = self.cell(center + size / 2.);
+        self.boxes.push(Entry {
+            min_cell: min,
+            layer,
+            mask,
+        });
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+        index
+    }
+
+    /// Calls `f` once for every pair of boxes sharing at least one cell where either
+    /// one's mask has a bit of the other's layer, lower index first.
+    pub fn for_each_pair(&self, mut f: impl FnMut(usize, usize)) {
+        for (&cell, indices) in self.cells.iter() {
+            for (k, &i) in indices.iter().enumerate() {
+                let a = &self.boxes[i];
+                for &j in indices[k + 1..].iter() {
+                    let b = &self.boxes[j];
+                    if a.mask & b.layer == 0 && b.mask & a.layer == 0 {
+                        continue;
+                    }
+                    // boxes sharing several cells are only reported from the first shared one
+                    let first_shared = (
+                        a.min_cell.0.max(b.min_cell.0),
+                        a.min_cell.1.max(b.min_cell.1),
+                    );
+                    if first_shared == cell {
+                        f(i, j);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Cell coordinates are small trusted integers, so they skip the default DoS resistant hash.
+#[derive(Default)]
+pub struct CellHasher(u64);
+
+impl Hasher for CellHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u64(n as u32 as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(grid: &SpatialHash) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        grid.for_each_pair(|i, j| pairs.push((i, j)));
+        pairs.sort_unstable();
+        pairs
+    }
+
+    #[test]
+    fn boxes_sharing_several_cells_are_paired_once() {
+        let mut grid = SpatialHash::new(10.);
+        grid.insert(Vec2::new(0., 0.), Vec2::new(15., 15.), 1, 1);
+        grid.insert(Vec2::new(2., 2.), Vec2::new(15., 15.), 1, 1);
+        assert_eq!(pairs(&grid), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn boxes_in_different_cells_are_not_paired() {
+        let mut grid = SpatialHash::new(10.);
+        grid.insert(Vec2::new(0., 0.), Vec2::new(4., 4.), 1, 1);
+        grid.insert(Vec2::new(50., 0.), Vec2::new(4., 4.), 1, 1);
+        grid.insert(Vec2::new(0., -50.), Vec2::new(4., 4.), 1, 1);
+        assert!(pairs(&grid).is_empty());
+    }
+
+    #[test]
+    fn either_mask_is_enough_to_pair() {
+        let mut grid = SpatialHash::new(10.);
+        grid.insert(Vec2::ZERO, Vec2::splat(4.), 1 << 0, 1 << 1);
+        grid.insert(Vec2::ZERO, Vec2::splat(4.), 1 << 1, 0);
+        grid.insert(Vec2::ZERO, Vec2::splat(4.), 1 << 2, 0);
+        assert_eq!(pairs(&grid), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn indices_follow_insertion_order() {
+        let mut grid = SpatialHash::new(10.);
+        assert_eq!(grid.insert(Vec2::ZERO, Vec2::splat(4.), 1, 1), 0);
+        assert_eq!(grid.insert(Vec2::ZERO, Vec2::splat(4.), 1, 1), 1);
+        assert_eq!(pairs(&grid), vec![(0, 1)]);
+    }
+}
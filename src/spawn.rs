@@ -0,0 +1,276 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    console::{parse_arg, ConsoleAppExt},
+    enemy::{spawn_enemy, EnemyTypes},
+    events::{DespawnCause, Despawned},
+    lifetime::Lifetime,
+    population::{Population, Tracked},
+    Block, Enemy, Materials, SpawnTelegraph, WindowSize,
+};
+
+/// Nothing spawns closer than this to the player.
+pub const SPAWN_SAFE_RADIUS: f32 = 250.;
+/// Room kept free around a spawning enemy, whatever its sprite size.
+pub const ENEMY_CLEARANCE: f32 = 100.;
+/// Tries around the wanted position before giving up for this frame.
+const SPAWN_ATTEMPTS: u32 = 12;
+/// How much further from the wanted position each retry may land.
+const SPAWN_JITTER: f32 = 40.;
+/// Seconds an enemy is telegraphed before it appears.
+const TELEGRAPH_TIME: f32 = 0.8;
+/// Telegraph blinks per second.
+const TELEGRAPH_BLINK: f32 = 8.;
+
+pub struct SpawnPlugin;
+
+impl Plugin for SpawnPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(GameRng(StdRng::from_entropy()))
+            .add_console_command("seed", "seed <n>: reseeds spawning", console_seed)
+            .add_system(telegraph_blink.system())
+            .add_system(spawn_telegraph.system().after("lifetime"));
+    }
+}
+
+/// Randomness behind spawning, shared so a run can be replayed from a seed.
+pub struct GameRng(pub StdRng);
+
+/// A random position inside the window, away from its edges.
+pub fn random_position<R: Rng>(win_size: &WindowSize, rng: &mut R) -> Vec2 {
+    let w_span = win_size.width / 2. - 100.;
+    let h_span = win_size.height / 2. - 100.;
+    Vec2::new(
+        rng.gen_range(-w_span..w_span),
+        rng.gen_range(-h_span..h_span),
+    )
+}
+
+/// Colliders spawn placement keeps clear of.
+pub type SpawnBlockers = Or<(With<Block>, With<Enemy>, With<SpawnTelegraph>)>;
+
+/// What is already taken when picking spawn positions: the rects of blocks, enemies
+/// and pending telegraphs, and the player's position.
+pub struct Placement {
+    occupied: Vec<(Vec2, Vec2)>,
+    player: Option<Vec2>,
+}
+
+impl Placement {
+    pub fn new<'a>(
+        colliders: impl Iterator<Item = (&'a GlobalTransform, &'a Sprite)>,
+        player: Option<Vec2>,
+    ) -> Self {
+        let occupied = colliders
+            .map(|(tf, sprite)| {
+                (
+                    tf.translation.truncate(),
+                    sprite.size * Vec2::from(tf.scale.abs()),
+                )
+            })
+            .collect();
+        Self { occupied, player }
+    }
+
+    /// True when a `size` rect at `position` overlaps nothing and is far enough from the player.
+    pub fn is_clear(&self, position: Vec2, size: Vec2) -> bool {
+        if let Some(player) = self.player {
+            if (player - position).length() < SPAWN_SAFE_RADIUS {
+                return false;
+            }
+        }
+        self.occupied.iter().all(|&(other, other_size)| {
+            let reach = (size + other_size) / 2.;
+            let distance = (other - position).abs();
+            distance.x >= reach.x || distance.y >= reach.y
+        })
+    }
+
+    /// Finds a clear spot at or around `wanted`, staying inside the window, and reserves it.
+    /// Returns `None` when every try is taken, callers retry later.
+    pub fn place<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        wanted: Vec2,
+        size: Vec2,
+        win_size: &WindowSize,
+    ) -> Option<Vec2> {
+        let w_span = win_size.width / 2. - size.x / 2.;
+        let h_span = win_size.height / 2. - size.y / 2.;
+        let position = (0..=SPAWN_ATTEMPTS)
+            .map(|attempt| {
+                if attempt == 0 {
+                    return wanted;
+                }
+                let reach = SPAWN_JITTER * attempt as f32;
+                let candidate =
+                    wanted + Vec2::new(rng.gen_range(-reach..reach), rng.gen_range(-reach..reach));
+                Vec2::new(
+                    candidate.x.max(-w_span).min(w_span),
+                    candidate.y.max(-h_span).min(h_span),
+                )
+            })
+            .find(|&position| self.is_clear(position, size))?;
+        self.occupied.push((position, size));
+        Some(position)
+    }
+}
+
+/// Marks where an enemy of type `kind` is about to appear. The telegraph counts as
+/// an active enemy and is swapped for the real one by `spawn_telegraph`.
+pub fn spawn_enemy_telegraph(
+    commands: &mut Commands,
+    materials: &Materials,
+    kind: usize,
+    position: Vec2,
+    patrol_block: Option<Entity>,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.telegraph.clone(),
+            sprite: Sprite::new(Vec2::splat(ENEMY_CLEARANCE)),
+            transform: Transform::from_translation(position.extend(5.)),
+            visible: Visible {
+                is_visible: true,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(SpawnTelegraph { kind, patrol_block })
+        .insert(Lifetime(TELEGRAPH_TIME))
+        .id()
+}
+
+fn telegraph_blink(mut query: Query<(&Lifetime, &mut Visible), With<SpawnTelegraph>>) {
+    for (lifetime, mut visible) in query.iter_mut() {
+        // on for one blink, off for the next
+        visible.is_visible = (lifetime.0 * TELEGRAPH_BLINK / 2.).fract() < 0.5;
+    }
+}
+
+/// Swaps expired telegraphs for their enemy, the telegraph itself is despawned
+/// like anything else whose `Lifetime` ran out.
+fn spawn_telegraph(
+    mut commands: Commands,
+    enemy_types: Res<EnemyTypes>,
+    mut population: ResMut<Population>,
+    mut despawned: EventReader<Despawned>,
+    query: Query<(&SpawnTelegraph, &Transform)>,
+    block_query: Query<Entity, With<Block>>,
+) {
+    for despawned in despawned.iter() {
+        if despawned.cause != DespawnCause::Expired {
+            continue;
+        }
+        let (telegraph, tf) = match query.get(despawned.entity) {
+            Ok(telegraph) => telegraph,
+            Err(_) => continue,
+        };
+        // the block may have been destroyed while the enemy was telegraphed
+        let patrol_block = telegraph
+            .patrol_block
+            .filter(|&block| block_query.get(block).is_ok());
+        let enemy = spawn_enemy(
+            &mut commands,
+            &enemy_types,
+            telegraph.kind,
+            tf.translation.truncate(),
+            patrol_block,
+        );
+        // counted in the telegraph's place before anything else spawns this frame
+        population.track(enemy, Tracked::Enemy(telegraph.kind));
+    }
+}
+
+fn console_seed(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let seed: u64 = parse_arg(args, 0, "seed")?;
+    world.insert_resource(GameRng(StdRng::seed_from_u64(seed)));
+    Ok(format!("seed {}", seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn win_size() -> WindowSize {
+        WindowSize {
+            width: 1000.,
+            height: 1000.,
+        }
+    }
+
+    fn block(position: Vec2, size: f32) -> (GlobalTransform, Sprite) {
+        (
+            GlobalTransform::from_translation(position.extend(0.)),
+            Sprite::new(Vec2::splat(size)),
+        )
+    }
+
+    fn placement(blocks: &[(GlobalTransform, Sprite)], player: Option<Vec2>) -> Placement {
+        Placement::new(blocks.iter().map(|(tf, sprite)| (tf, sprite)), player)
+    }
+
+    #[test]
+    fn clear_spots_are_used_as_wanted() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut placement = placement(&[], None);
+        let wanted = Vec2::new(100., 200.);
+        let size = Vec2::splat(ENEMY_CLEARANCE);
+        assert_eq!(
+            placement.place(&mut rng, wanted, size, &win_size()),
+            Some(wanted)
+        );
+    }
+
+    #[test]
+    fn taken_spots_are_avoided_and_placed_ones_reserved() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let blocks = [block(Vec2::ZERO, 50.)];
+        let mut placement = placement(&blocks, None);
+        let size = Vec2::splat(ENEMY_CLEARANCE);
+
+        let first = placement
+            .place(&mut rng, Vec2::ZERO, size, &win_size())
+            .unwrap();
+        assert_ne!(first, Vec2::ZERO);
+        assert!(!placement.is_clear(first, size));
+        let second = placement.place(&mut rng, first, size, &win_size()).unwrap();
+        assert_ne!(second, first);
+    }
+
+    #[test]
+    fn jittered_spots_stay_inside_the_window() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let corner = Vec2::new(450., 450.);
+        let blocks = [block(corner, 50.)];
+        let size = Vec2::splat(ENEMY_CLEARANCE);
+        for _ in 0..20 {
+            let mut placement = placement(&blocks, None);
+            let position = placement
+                .place(&mut rng, corner, size, &win_size())
+                .unwrap();
+            assert!(position.x <= 450. && position.y <= 450.);
+        }
+    }
+
+    #[test]
+    fn nothing_is_placed_next_to_the_player() {
+        let placement = placement(&[], Some(Vec2::ZERO));
+        let size = Vec2::splat(ENEMY_CLEARANCE);
+        assert!(!placement.is_clear(Vec2::new(SPAWN_SAFE_RADIUS - 1., 0.), size));
+        assert!(placement.is_clear(Vec2::new(SPAWN_SAFE_RADIUS + 1., 0.), size));
+    }
+
+    #[test]
+    fn gives_up_when_everything_is_taken() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let blocks = [block(Vec2::ZERO, 3000.)];
+        let mut placement = placement(&blocks, None);
+        let size = Vec2::splat(ENEMY_CLEARANCE);
+        assert_eq!(
+            placement.place(&mut rng, Vec2::ZERO, size, &win_size()),
+            None
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+
+use bevy::{math::const_vec2, prelude::*};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    boss::{spawn_boss, BossTypes},
+    data::load_ron,
+    director::Director,
+    enemy::{BehaviorKind, EnemyTypes},
+    game_time::GameTime,
+    level::CurrentLevel,
+    population::{Population, Tracked},
+    spawn::{
+        random_position, spawn_enemy_telegraph, GameRng, Placement, SpawnBlockers, ENEMY_CLEARANCE,
+    },
+    Ability, Block, Boss, Materials, Player, PlayerAbilities, PlayerState, WindowSize, WAVES_FILE,
+};
+
+/// Room kept free around a spawning boss.
+const BOSS_CLEARANCE: Vec2 = const_vec2!([300., 150.]);
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<WaveCleared>()
+            .add_startup_system(wave_script_load.system())
+            .add_system(wave_progress.system())
+            .add_system(wave_rewards.system())
+            .add_system(wave_spawn.system());
+    }
+}
+
+/// Sent when every enemy of a wave has been spawned and none are left alive,
+/// including the wave's boss if it has one.
+pub struct WaveCleared {
+    pub wave: usize,
+}
+
+#[derive(Deserialize)]
+pub struct WaveScript {
+    pub break_time: f32,
+    pub waves: Vec<Wave>,
+}
+
+#[derive(Deserialize)]
+pub struct Wave {
+    pub groups: Vec<SpawnGroup>,
+    /// Boss type fought once every group of the wave is cleared.
+    #[serde(default)]
+    pub boss: Option<String>,
+    /// Level file switched to when the break before the wave starts, the previous
+    /// level is kept otherwise.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Abilities the player unlocks for clearing the wave the first time through the script.
+    #[serde(default)]
+    pub unlocks: Vec<Ability>,
+}
+
+/// `count` enemies of type `enemy`, queued `delay` seconds after the wave starts.
+#[derive(Deserialize)]
+pub struct SpawnGroup {
+    pub enemy: String,
+    pub count: u32,
+    pub formation: Formation,
+    #[serde(default)]
+    pub delay: f32,
+}
+
+#[derive(Deserialize)]
+pub enum Formation {
+    Random,
+    /// A horizontal line centered on the window.
+    Line {
+        y: f32,
+        spacing: f32,
+    },
+    /// Rows of `columns` enemies going right and down from `origin`.
+    Grid {
+        columns: u32,
+        origin: (f32, f32),
+        spacing: (f32, f32),
+    },
+    /// Fixed positions, reused in order when `count` is larger.
+    At(Vec<(f32, f32)>),
+}
+
+impl Formation {
+    fn positions<R: Rng>(&self, count: u32, win_size: &WindowSize, rng: &mut R) -> Vec<Vec2> {
+        match self {
+            Formation::Random => (0..count).map(|_| random_position(win_size, rng)).collect(),
+            Formation::Line { y, spacing } => {
+                let width = spacing * count.saturating_sub(1) as f32;
+                (0..count)
+                    .map(|i| Vec2::new(-width / 2. + i as f32 * spacing, *y))
+                    .collect()
+            }
+            Formation::Grid {
+                columns,
+                origin,
+                spacing,
+            } => {
+                let columns = (*columns).max(1);
+                (0..count)
+                    .map(|i| {
+                        Vec2::new(
+                            origin.0 + (i % columns) as f32 * spacing.0,
+                            origin.1 - (i / columns) as f32 * spacing.1,
+                        )
+                    })
+                    .collect()
+            }
+            Formation::At(points) => points
+                .iter()
+                .cycle()
+                .take(count as usize)
+                .map(|&(x, y)| Vec2::new(x, y))
+                .collect(),
+        }
+    }
+}
+
+pub enum WavePhase {
+    /// Seconds left before the next wave starts.
+    Break(f32),
+    /// Seconds since the wave started and the next group waiting for its delay.
+    Running { elapsed: f32, next_group: usize },
+    /// Waiting for the wave's boss to be defeated.
+    Boss,
+}
+
+/// `wave` counts up forever, the script loops once every wave has been played.
+pub struct WaveState {
+    pub wave: usize,
+    pub phase: WavePhase,
+    queue: VecDeque<(usize, Vec2)>,
+}
+
+fn wave_script_load(mut commands: Commands) {
+    let script: WaveScript = load_ron(WAVES_FILE);
+    commands.insert_resource(WaveState {
+        wave: 0,
+        phase: WavePhase::Break(script.break_time),
+        queue: VecDeque::new(),
+    });
+    commands.insert_resource(script);
+}
+
+fn wave_progress(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut state: ResMut<WaveState>,
+    script: Res<WaveScript>,
+    enemy_types: Res<EnemyTypes>,
+    boss_types: Res<BossTypes>,
+    win_size: Res<WindowSize>,
+    population: Res<Population>,
+    mut game_rng: ResMut<GameRng>,
+    mut current_level: ResMut<CurrentLevel>,
+    boss_query: Query<Entity, With<Boss>>,
+    collider_query: Query<(&GlobalTransform, &Sprite), SpawnBlockers>,
+    player_query: Query<&Transform, With<Player>>,
+    mut wave_cleared: EventWriter<WaveCleared>,
+) {
+    if script.waves.is_empty() {
+        return;
+    }
+    let rng = &mut game_rng.0;
+    let state: &mut WaveState = &mut state;
+    let wave = &script.waves[state.wave % script.waves.len()];
+
+    match &mut state.phase {
+        WavePhase::Break(left) => {
+            if let Some(level) = &wave.level {
+                if current_level.0 != *level {
+                    current_level.0 = level.clone();
+                }
+            }
+            *left -= game_time.delta();
+            if *left <= 0. {
+                state.phase = WavePhase::Running {
+                    elapsed: 0.,
+                    next_group: 0,
+                };
+            }
+        }
+        WavePhase::Running {
+            elapsed,
+            next_group,
+        } => {
+            *elapsed += game_time.delta();
+            while *next_group < wave.groups.len() && wave.groups[*next_group].delay <= *elapsed {
+                let group = &wave.groups[*next_group];
+                match enemy_types.by_name(&group.enemy) {
+                    Some(kind) => {
+                        for position in group.formation.positions(group.count, &win_size, rng) {
+                            state.queue.push_back((kind, position));
+                        }
+                    }
+                    None => warn!("{} names unknown enemy type {}", WAVES_FILE, group.enemy),
+                }
+                *next_group += 1;
+            }
+
+            if *next_group == wave.groups.len()
+                && state.queue.is_empty()
+                && population.enemies() == 0
+            {
+                let boss = wave.boss.as_ref().and_then(|name| {
+                    let kind = boss_types.by_name(name);
+                    if kind.is_none() {
+                        warn!("{} names unknown boss type {}", WAVES_FILE, name);
+                    }
+                    kind
+                });
+                match boss {
+                    Some(kind) => {
+                        let wanted = Vec2::new(0., win_size.height / 2. - 150.);
+                        let mut placement = Placement::new(
+                            collider_query.iter(),
+                            player_query
+                                .single()
+                                .ok()
+                                .map(|tf| tf.translation.truncate()),
+                        );
+                        // the boss can't wait for room, it spawns where it was meant to if it must
+                        let position = placement
+                            .place(rng, wanted, BOSS_CLEARANCE, &win_size)
+                            .unwrap_or(wanted);
+                        spawn_boss(&mut commands, &boss_types, kind, position);
+                        state.phase = WavePhase::Boss;
+                    }
+                    None => finish_wave(state, &script, &mut wave_cleared),
+                }
+            }
+        }
+        WavePhase::Boss => {
+            // the boss entity only goes away once its death explosions are over
+            if boss_query.iter().next().is_none() {
+                finish_wave(state, &script, &mut wave_cleared);
+            }
+        }
+    }
+}
+
+fn finish_wave(
+    state: &mut WaveState,
+    script: &WaveScript,
+    wave_cleared: &mut EventWriter<WaveCleared>,
+) {
+    wave_cleared.send(WaveCleared {
+        wave: state.wave + 1,
+    });
+    state.wave += 1;
+    state.phase = WavePhase::Break(script.break_time);
+}
+
+fn wave_rewards(
+    script: Res<WaveScript>,
+    mut player_query: Query<&mut PlayerAbilities, With<Player>>,
+    mut player_state: ResMut<PlayerState>,
+    mut wave_cleared: EventReader<WaveCleared>,
+) {
+    for cleared in wave_cleared.iter() {
+        // `wave` counts from 1, and a looping script doesn't hand out the same rewards again
+        if let Some(wave) = script.waves.get(cleared.wave - 1) {
+            for &ability in wave.unlocks.iter() {
+                info!("wave {} cleared, unlocked {:?}", cleared.wave, ability);
+                match player_query.single_mut() {
+                    Ok(mut abilities) => abilities.unlock(ability),
+                    // dead, the next player gets it
+                    Err(_) => player_state.abilities.unlock(ability),
+                }
+            }
+        }
+    }
+}
+
+/// Every spawn interval, telegraphs queued enemies while there is room under the enemy cap
+/// and their type's own cap.
+/// Enemies whose spot is taken stay queued until it clears up.
+fn wave_spawn(
+    game_time: Res<GameTime>,
+    mut commands: Commands,
+    mut since_spawn: Local<f32>,
+    mut state: ResMut<WaveState>,
+    mut population: ResMut<Population>,
+    mut game_rng: ResMut<GameRng>,
+    director: Res<Director>,
+    enemy_types: Res<EnemyTypes>,
+    materials: Res<Materials>,
+    win_size: Res<WindowSize>,
+    block_query: Query<(Entity, &GlobalTransform, &Sprite), With<Block>>,
+    collider_query: Query<(&GlobalTransform, &Sprite), SpawnBlockers>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    *since_spawn += game_time.delta();
+    if *since_spawn < director.spawn_interval() {
+        return;
+    }
+    *since_spawn = 0.;
+
+    let rng = &mut game_rng.0;
+    let mut placement = Placement::new(
+        collider_query.iter(),
+        player_query
+            .single()
+            .ok()
+            .map(|tf| tf.translation.truncate()),
+    );
+    let enemy_size = Vec2::splat(ENEMY_CLEARANCE);
+    let mut blocks: Vec<(Entity, Vec2)> = block_query
+        .iter()
+        .map(|(entity, tf, sprite)| {
+            let size = sprite.size * Vec2::from(tf.scale.abs());
+            let top = tf.translation.truncate() + Vec2::new(0., (size.y + enemy_size.y) / 2.);
+            (entity, top)
+        })
+        .collect();
+    while population.enemies() < director.enemy_cap() {
+        // the first queued enemy whose type isn't at its cap goes next
+        let next = state.queue.iter().position(|&(kind, _)| {
+            match enemy_types.types[kind].spec.max_alive {
+                Some(max_alive) => population.enemies_of_kind(kind) < max_alive,
+                None => true,
+            }
+        });
+        let (index, (kind, wanted)) = match next {
+            Some(index) => (index, state.queue[index]),
+            None => break,
+        };
+
+        // patrollers start on top of a random free block, others around their formation spot
+        let mut patrol_block = None;
+        let mut position = None;
+        if let BehaviorKind::Patrol = enemy_types.types[kind].spec.behavior {
+            while !blocks.is_empty() && position.is_none() {
+                let (block, top) = blocks.swap_remove(rng.gen_range(0..blocks.len()));
+                if placement.is_clear(top, enemy_size) {
+                    patrol_block = Some(block);
+                    position = placement.place(rng, top, enemy_size, &win_size);
+                }
+            }
+        }
+        if position.is_none() {
+            position = placement.place(rng, wanted, enemy_size, &win_size);
+        }
+        let position = match position {
+            Some(position) => position,
+            None => break,
+        };
+
+        state.queue.remove(index);
+        let telegraph =
+            spawn_enemy_telegraph(&mut commands, &materials, kind, position, patrol_block);
+        population.track(telegraph, Tracked::Enemy(kind));
+    }
+}